@@ -88,10 +88,9 @@ impl Bitboard {
         Self { bits: self.bits | other.bits }
     }
 
-    /*
     pub fn xor(&self, other: Bitboard) -> Self {
         Self { bits: self.bits ^ other.bits }
-    }*/
+    }
 }
 #[inline(always)]
 pub fn not(bitboard: Bitboard) -> Bitboard {
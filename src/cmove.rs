@@ -32,6 +32,18 @@ impl Move {
         Self { data: data }
     }
 
+    ///A piece dropped from the pocket in Crazyhouse. It comes from and goes to the same square
+    pub fn new_drop(square: u8, piece: u8) -> Self {
+        let mut data: u32 = square as u32;
+
+        data |= (square as u32) << 6;
+        data |= (piece as u32) << 12;
+        data |= (Piece::None as u32) << 16;
+        data |= 0x1000000;
+
+        Self { data }
+    }
+
     /*pub fn new_from_u32(data: u32) -> Self{
         Self { data: data }
     }*/
@@ -81,7 +93,15 @@ impl Move {
         (self.data & 0x800000) == 0x800000
     }
 
+    pub fn is_drop(&self) -> bool {
+        (self.data & 0x1000000) == 0x1000000
+    }
+
     pub fn to_uci(&self) -> String {
+        if self.is_drop() {
+            return format!("{}@{}", PIECE_STRINGS[(self.piece() % 6) as usize], SQUARE_STRINGS[self.to_square() as usize]);
+        }
+
         let mut result = SQUARE_STRINGS[self.from_square() as usize].to_string();
         result += SQUARE_STRINGS[self.to_square() as usize];
         if self.promotion() != Piece::None as u8 {
@@ -110,6 +130,9 @@ impl Move {
         if self.is_double_push() {
             print!("    Double push")
         }
+        if self.is_drop() {
+            print!("    Drop")
+        }
         println!()
     }
 }
\ No newline at end of file
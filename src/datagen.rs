@@ -0,0 +1,231 @@
+use std::{fs::OpenOptions, io::{BufWriter, Write}, sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::SystemTime};
+
+use rayon::prelude::*;
+
+use super::*;
+
+const RANDOM_PLIES: usize = 8;
+const DATAGEN_TT_MB: usize = 4;
+const MAX_GAME_PLIES: usize = 400;
+
+//Adjudication
+const RESIGN_SCORE: i32 = 1500;
+const RESIGN_PLIES: usize = 4;
+const DRAW_SCORE: i32 = 10;
+const DRAW_PLIES: usize = 12;
+const DRAW_MIN_PLIES: usize = 80;
+
+#[derive(Clone, Copy, PartialEq)]
+enum GameResult {
+    WhiteWin,
+    Draw,
+    BlackWin,
+}
+
+impl GameResult {
+    fn as_str(&self) -> &str {
+        match self {
+            GameResult::WhiteWin => "1.0",
+            GameResult::Draw => "0.5",
+            GameResult::BlackWin => "0.0",
+        }
+    }
+
+    fn as_pgn(&self) -> &str {
+        match self {
+            GameResult::WhiteWin => "1-0",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::BlackWin => "0-1",
+        }
+    }
+}
+
+///Plays fixed-node self-play games from random openings, and appends the positions to `path`.
+///Every line is "fen | score | result", where the score is in centipawns and the result is 1.0/0.5/0.0, both from white's perspective.
+///The games are also appended to `pgn_path` with the evaluations as comments, if given
+pub fn datagen(games: u64, nodes: u64, path: &str, pgn_path: Option<&str>) {
+    let file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(f) => f,
+        Err(e) => { println!(" Could not open '{}': {}", path, e); return; }
+    };
+    let writer = Mutex::new(BufWriter::new(file));
+
+    let pgn_writer = match pgn_path.map(|p| OpenOptions::new().create(true).append(true).open(p)) {
+        Some(Ok(f)) => Some(Mutex::new(BufWriter::new(f))),
+        Some(Err(e)) => { println!(" Could not open '{}': {}", pgn_path.unwrap(), e); return; }
+        None => None
+    };
+
+    let start = SystemTime::now();
+    let games_done = AtomicU64::new(0);
+    let positions_done = AtomicU64::new(0);
+
+    println!(" Generating {} games at {} nodes per move into '{}'...", games, nodes, path);
+
+    (0..games).into_par_iter().for_each(|_| {
+        let mut tt = TranspositionTable::with_size(DATAGEN_TT_MB);
+        let (positions, result, mut pgn) = play_game(nodes, &mut tt);
+        pgn.set_result(result.as_pgn());
+
+        {
+            let mut writer = writer.lock().unwrap();
+            for (fen, score) in positions.iter() {
+                writeln!(writer, "{} | {} | {}", fen, score, result.as_str()).expect("Could not write training data");
+            }
+        }
+
+        if let Some(pgn_writer) = &pgn_writer {
+            writeln!(pgn_writer.lock().unwrap(), "{}", pgn.to_pgn()).expect("Could not write games");
+        }
+
+        let done = games_done.fetch_add(1, Ordering::Relaxed) + 1;
+        let total = positions_done.fetch_add(positions.len() as u64, Ordering::Relaxed) + positions.len() as u64;
+        if done.is_multiple_of(100) || done == games {
+            let seconds = start.elapsed().unwrap().as_secs_f64().max(0.001);
+            println!(" {} games, {} positions, {:.0} positions/s", done, total, total as f64 / seconds);
+        }
+    });
+
+    writer.lock().unwrap().flush().expect("Could not write training data");
+    if let Some(pgn_writer) = &pgn_writer {
+        pgn_writer.lock().unwrap().flush().expect("Could not write games");
+    }
+    println!(" Done with datagen!");
+}
+
+///Plays one game, returning the positions worth training on, the final result and the game from the end of the opening
+fn play_game(nodes: u64, tt: &mut TranspositionTable) -> (Vec<(String, i32)>, GameResult, PgnGame) {
+    let (mut game, mut rep_table) = random_opening();
+    let mut evaluator = ClassicEvaluator::new();
+
+    let mut pgn = PgnGame::new(&game);
+    pgn.set_tag("Event", "JENCE datagen");
+    pgn.set_tag("White", "JENCE");
+    pgn.set_tag("Black", "JENCE");
+
+    let mut positions = Vec::new();
+    let mut history: Vec<u64> = vec![game.zobrist_hash];
+
+    let mut resign_count = 0;
+    let mut draw_count = 0;
+
+    for ply in 0..MAX_GAME_PLIES {
+        if let Some(result) = adjudicate_rules(&mut game, &history) {
+            return (positions, result, pgn);
+        }
+
+        let result = search_nodes(&mut game, -1, nodes, tt, &mut rep_table, &mut evaluator);
+        let best = result.best_move;
+
+        //Score from white's perspective
+        let score = if game.active_player == Color::White { result.score } else { -result.score };
+
+        //Resignation and draw adjudication
+        if score.abs() > MATE_BOUND {
+            return (positions, if score > 0 { GameResult::WhiteWin } else { GameResult::BlackWin }, pgn);
+        }
+        resign_count = if score.abs() >= RESIGN_SCORE { resign_count + 1 } else { 0 };
+        if resign_count >= RESIGN_PLIES {
+            return (positions, if score > 0 { GameResult::WhiteWin } else { GameResult::BlackWin }, pgn);
+        }
+        draw_count = if score.abs() <= DRAW_SCORE { draw_count + 1 } else { 0 };
+        if ply >= DRAW_MIN_PLIES && draw_count >= DRAW_PLIES {
+            return (positions, GameResult::Draw, pgn);
+        }
+
+        //Only quiet positions make good training data
+        if !game.is_in_check(game.active_player) && !best.is_capture() && best.promotion() == Piece::None as u8 {
+            positions.push((game.to_fen(), score));
+        }
+
+        pgn.push_move(best, Some(eval_comment(score, result.depth)));
+        make_search_move(&mut game, &best, &mut rep_table);
+        history.push(game.zobrist_hash);
+    }
+
+    (positions, GameResult::Draw, pgn)
+}
+
+///Start position followed by a few random plies. Retries until the opening leaves legal moves on the board
+fn random_opening() -> (Game, RepetitionTable) {
+    'retry: loop {
+        let mut game = Game::new_from_start_pos();
+        let mut rep_table = RepetitionTable::new();
+
+        for _ in 0..RANDOM_PLIES {
+            match random_legal_move(&mut game) {
+                Some(m) => { make_search_move(&mut game, &m, &mut rep_table); },
+                None => continue 'retry
+            }
+        }
+
+        if generate_moves(&mut game, MoveTypes::All).legal_values(&game).is_empty() {
+            continue;
+        }
+
+        return (game, rep_table);
+    }
+}
+
+///Checkmate, stalemate, 50 move rule, threefold repetition and insufficient material
+fn adjudicate_rules(game: &mut Game, history: &[u64]) -> Option<GameResult> {
+    if generate_moves(game, MoveTypes::All).legal_values(game).is_empty() {
+        if !game.is_in_check(game.active_player) {
+            return Some(GameResult::Draw);
+        }
+        return Some(if game.active_player == Color::White { GameResult::BlackWin } else { GameResult::WhiteWin });
+    }
+
+    if game.half_moves >= 100 || history.iter().filter(|h| **h == game.zobrist_hash).count() >= 3 || is_insufficient_material(game) {
+        return Some(GameResult::Draw);
+    }
+
+    None
+}
+
+fn is_insufficient_material(game: &Game) -> bool {
+    let heavy = [Piece::WhitePawn, Piece::WhiteRook, Piece::WhiteQueen, Piece::BlackPawn, Piece::BlackRook, Piece::BlackQueen];
+    if heavy.iter().any(|p| !game.get_piece_bitboard(*p).is_empty()) {
+        return false;
+    }
+
+    let mut minors = game.get_piece_bitboard(Piece::WhiteKnight)
+        .or(game.get_piece_bitboard(Piece::WhiteBishop))
+        .or(game.get_piece_bitboard(Piece::BlackKnight))
+        .or(game.get_piece_bitboard(Piece::BlackBishop));
+
+    minors.pop_count() <= 1
+}
+
+#[cfg(test)]
+mod datagen_tests {
+    use super::*;
+
+    #[test]
+    pub fn adjudicates_checkmate_and_draws() {
+        let mut mated = Game::new_from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(adjudicate_rules(&mut mated, &[]) == Some(GameResult::WhiteWin));
+
+        let mut stalemate = Game::new_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(adjudicate_rules(&mut stalemate, &[]) == Some(GameResult::Draw));
+
+        let mut bare = Game::new_from_fen("8/8/4k3/8/8/3NK3/8/8 w - - 0 1").unwrap();
+        assert!(adjudicate_rules(&mut bare, &[]) == Some(GameResult::Draw));
+
+        let mut playing = Game::new_from_start_pos();
+        assert!(adjudicate_rules(&mut playing, &[]).is_none());
+    }
+
+    #[test]
+    pub fn plays_a_game_to_the_end() {
+        let (positions, result, mut pgn) = play_game(500, &mut TranspositionTable::with_size(1));
+        pgn.set_result(result.as_pgn());
+        assert!(!positions.is_empty());
+        assert!(positions.iter().all(|(fen, _)| Game::new_from_fen(fen).is_some()));
+
+        //The written game reads back with the same moves
+        let read = read_pgn(&pgn.to_pgn()).remove(0).unwrap();
+        assert_eq!(read.result, result.as_pgn());
+        assert_eq!(read.moves.len(), pgn.moves.len());
+    }
+}
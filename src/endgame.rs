@@ -0,0 +1,233 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use super::*;
+
+///Scale factors applied to the evaluation of the stronger side
+pub const SCALE_NORMAL: i32 = 64;
+pub const SCALE_DRAW: i32 = 0;
+const SCALE_OPPOSITE_BISHOPS: i32 = 32;
+const SCALE_NO_PAWNS: i32 = 16;
+
+//Base score of a won endgame, well below the mate scores
+const KNOWN_WIN: i32 = 5000;
+
+///Dedicated evaluation of an endgame, from the perspective of the strong side
+pub type EndgameFn = fn(&Game, Color) -> i32;
+
+//Material strings are white's pieces, "v", then black's pieces. Every entry is also added with the colors swapped
+const ENDGAMES: [(&str, EndgameFn); 7] = [
+    ("KvK", eval_draw),
+    ("KNvK", eval_draw),
+    ("KBvK", eval_draw),
+    ("KNNvK", eval_draw),
+    ("KRvK", eval_kxk),
+    ("KQvK", eval_kxk),
+    ("KBNvK", eval_kbnk),
+];
+
+static ENDGAME_TABLE: OnceLock<HashMap<u64, (EndgameFn, Color)>> = OnceLock::new();
+
+// bonus for driving the losing king to the edge
+const PUSH_TO_EDGE: [i32; 64] =
+[
+    100,  90,  80,  70,  70,  80,  90, 100,
+     90,  70,  60,  50,  50,  60,  70,  90,
+     80,  60,  40,  30,  30,  40,  60,  80,
+     70,  50,  30,  20,  20,  30,  50,  70,
+     70,  50,  30,  20,  20,  30,  50,  70,
+     80,  60,  40,  30,  30,  40,  60,  80,
+     90,  70,  60,  50,  50,  60,  70,  90,
+    100,  90,  80,  70,  70,  80,  90, 100
+];
+
+// bonus for keeping the kings close, indexed by their distance
+const PUSH_CLOSE: [i32; 8] = [ 0, 0, 100, 80, 60, 40, 20, 10 ];
+
+///Counts of every piece but the kings, 4 bits each
+pub fn material_signature(game: &Game) -> u64 {
+    let mut signature = 0;
+    for (i, piece) in [0, 1, 2, 3, 4, 6, 7, 8, 9, 10].iter().enumerate() {
+        signature |= (game.bitboards[*piece].to_u64().count_ones() as u64) << (4 * i);
+    }
+    signature
+}
+
+///Signature of a material string such as "KBNvK"
+pub fn signature_from_str(material: &str) -> u64 {
+    let (white, black) = material.split_once('v').unwrap();
+    let mut signature = 0;
+    for (offset, side) in [(0, white), (5, black)] {
+        for c in side.chars().filter(|c| *c != 'K') {
+            let piece = "PNBRQ".find(c).unwrap();
+            signature += 1 << (4 * (offset + piece));
+        }
+    }
+    signature
+}
+
+fn endgame_table() -> &'static HashMap<u64, (EndgameFn, Color)> {
+    ENDGAME_TABLE.get_or_init(|| {
+        let mut table = HashMap::new();
+        for (material, evaluator) in ENDGAMES {
+            let (white, black) = material.split_once('v').unwrap();
+            table.insert(signature_from_str(material), (evaluator as EndgameFn, Color::White));
+            table.insert(signature_from_str(&format!("{}v{}", black, white)), (evaluator as EndgameFn, Color::Black));
+        }
+        table
+    })
+}
+
+///The dedicated evaluator and the strong side, if the material is a known endgame
+pub fn probe_endgame(game: &Game) -> Option<(EndgameFn, Color)> {
+    //Every endgame in the table is without pawns
+    if game.bitboards[Piece::WhitePawn as usize].is_empty() && game.bitboards[Piece::BlackPawn as usize].is_empty() {
+        if let Some(entry) = endgame_table().get(&material_signature(game)) {
+            return Some(*entry);
+        }
+    }
+
+    //Against a lone king, enough material wins by driving the king to the edge
+    for (strong, weak_occupancies) in [(Color::White, game.black_occupancies), (Color::Black, game.white_occupancies)] {
+        if weak_occupancies.to_u64().count_ones() == 1 && non_pawn_material(game, strong) >= MATERIAL_WEIGHTS[Piece::WhiteRook as usize] {
+            return Some((eval_kxk, strong));
+        }
+    }
+
+    None
+}
+
+///How much of the strong side's advantage remains with drawish material, out of SCALE_NORMAL
+pub fn scale_factor(game: &Game, strong: Color) -> i32 {
+    let (offset, weak_offset) = if strong == Color::White { (0, 6) } else { (6, 0) };
+    let weak = opposite_color(strong);
+
+    let strong_pawns = game.bitboards[offset].to_u64();
+    let strong_minors_and_majors = (1..5).map(|p| game.bitboards[offset + p].to_u64().count_ones()).sum::<u32>();
+    let weak_minors_and_majors = (1..5).map(|p| game.bitboards[weak_offset + p].to_u64().count_ones()).sum::<u32>();
+    let strong_bishops = game.bitboards[offset + 2].to_u64();
+    let weak_bishops = game.bitboards[weak_offset + 2].to_u64();
+
+    //Rook pawns with a bishop that does not control the promotion square, and the defending king in the corner
+    if strong_pawns != 0 && strong_bishops != 0 && strong_minors_and_majors == strong_bishops.count_ones() {
+        for file in [0, 7] {
+            if strong_pawns & !FILE_MASKS[file] != 0 {
+                continue;
+            }
+            let promotion = if strong == Color::White { file } else { 56 + file } as u8;
+            let controls_promotion = bitboard_squares(strong_bishops).any(|b| square_color(b) == square_color(promotion));
+            let weak_king = game.bitboards[weak_offset + 5];
+            if !controls_promotion && !weak_king.is_empty() && square_distance(weak_king.least_significant(), promotion) <= 1 {
+                return SCALE_DRAW;
+            }
+        }
+    }
+
+    //Opposite coloured bishops, with nothing else but pawns
+    if strong_minors_and_majors == 1 && weak_minors_and_majors == 1 && strong_bishops.count_ones() == 1 && weak_bishops.count_ones() == 1
+        && square_color(strong_bishops.trailing_zeros() as u8) != square_color(weak_bishops.trailing_zeros() as u8) {
+        return SCALE_OPPOSITE_BISHOPS;
+    }
+
+    //Without pawns, an advantage of a minor piece or less is rarely enough
+    if strong_pawns == 0 && non_pawn_material(game, strong) - non_pawn_material(game, weak) <= MATERIAL_WEIGHTS[Piece::WhiteBishop as usize] {
+        return SCALE_NO_PAWNS;
+    }
+
+    SCALE_NORMAL
+}
+
+fn eval_draw(_game: &Game, _strong: Color) -> i32 {
+    0
+}
+
+///King and mating material against a lone king: drive the king to the edge and bring the own king closer
+fn eval_kxk(game: &Game, strong: Color) -> i32 {
+    let (strong_king, weak_king) = king_squares(game, strong);
+    let material = non_pawn_material(game, strong) + game.bitboards[if strong == Color::White { 0 } else { 6 }].to_u64().count_ones() as i32 * MATERIAL_WEIGHTS[0];
+
+    KNOWN_WIN + material + PUSH_TO_EDGE[weak_king as usize] + PUSH_CLOSE[square_distance(strong_king, weak_king) as usize]
+}
+
+///King, bishop and knight against king: drive the king to a corner of the bishop's colour
+fn eval_kbnk(game: &Game, strong: Color) -> i32 {
+    let (strong_king, weak_king) = king_squares(game, strong);
+    let bishop = game.bitboards[if strong == Color::White { Piece::WhiteBishop } else { Piece::BlackBishop } as usize].least_significant();
+
+    //a8/h1 are light, a1/h8 are dark
+    let corners = if square_color(bishop) == square_color(Square::a1 as u8) { [Square::a1, Square::h8] } else { [Square::a8, Square::h1] };
+    let corner_distance = corners.iter().map(|c| square_distance(weak_king, *c as u8)).min().unwrap();
+
+    KNOWN_WIN + non_pawn_material(game, strong) + (7 - corner_distance) * 50 + PUSH_CLOSE[square_distance(strong_king, weak_king) as usize]
+}
+
+fn king_squares(game: &Game, strong: Color) -> (u8, u8) {
+    let white = game.bitboards[Piece::WhiteKing as usize].least_significant();
+    let black = game.bitboards[Piece::BlackKing as usize].least_significant();
+    if strong == Color::White { (white, black) } else { (black, white) }
+}
+
+///0 for light squares, 1 for dark squares
+#[inline(always)]
+fn square_color(square: u8) -> u8 {
+    (square / 8 + square % 8) % 2
+}
+
+fn bitboard_squares(mut bits: u64) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let square = bits.trailing_zeros() as u8;
+        bits &= bits - 1;
+        Some(square)
+    })
+}
+
+#[cfg(test)]
+mod endgame_tests {
+    use super::*;
+
+    fn eval(fen: &str) -> i32 {
+        evaluate(&Game::new_from_fen(fen).unwrap())
+    }
+
+    #[test]
+    pub fn insufficient_material_is_drawn() {
+        assert_eq!(eval("8/8/4k3/8/8/3NK3/8/8 w - - 0 1"), 0);
+        assert_eq!(eval("8/8/4k3/8/8/3bK3/8/8 w - - 0 1"), 0);
+        assert_eq!(eval("8/8/4k3/8/8/2NNK3/8/8 b - - 0 1"), 0);
+    }
+
+    #[test]
+    pub fn lone_king_is_driven_to_the_edge() {
+        let centre = eval("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+        let edge = eval("8/8/8/7k/8/8/8/R3K3 w - - 0 1");
+        assert!(centre > KNOWN_WIN);
+        assert!(edge > centre);
+
+        //Symmetric for black
+        assert_eq!(eval("r3k3/8/8/8/7K/8/8/8 b - - 0 1"), edge);
+    }
+
+    #[test]
+    pub fn bishop_and_knight_mate_in_the_right_corner() {
+        //Dark squared bishop on c1 mates on a1/h8
+        let right = eval("8/8/8/8/8/8/1K6/k1B1N3 w - - 0 1");
+        let wrong = eval("k7/8/8/8/8/8/1K6/2B1N3 w - - 0 1");
+        assert!(right > wrong);
+        assert!(eval("8/8/8/8/8/8/1K6/k1B1N3 w - - 0 1") > KNOWN_WIN);
+    }
+
+    #[test]
+    pub fn drawish_material_is_scaled() {
+        //h pawn with a light squared bishop does not queen on the dark h8 square
+        let game = Game::new_from_fen("7k/8/8/7P/8/8/3B4/4K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&game, Color::White), SCALE_NORMAL);
+        let game = Game::new_from_fen("7k/8/8/7P/8/8/4B3/4K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&game, Color::White), SCALE_DRAW);
+        assert_eq!(evaluate(&game), 0);
+
+        let game = Game::new_from_fen("4k3/5b2/8/1p6/1P2P3/8/3B4/4K3 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&game, Color::White), SCALE_OPPOSITE_BISHOPS);
+    }
+}
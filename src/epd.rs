@@ -0,0 +1,195 @@
+use std::{fs, time::SystemTime};
+
+use super::*;
+
+///A position of a test suite like WAC or STS, with the operations used to score the engine's move
+pub struct EpdEntry {
+    pub game: Game,
+    pub id: String,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+    pub centipawns: Option<i32>,
+    pub mate_in: Option<i32>,
+}
+
+impl EpdEntry {
+    ///Whether any of bm, am or dm says what a solution is
+    pub fn is_scored(&self) -> bool {
+        !self.best_moves.is_empty() || !self.avoid_moves.is_empty() || self.mate_in.is_some()
+    }
+
+    pub fn is_solution(&self, cmove: &Move, score: i32) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(cmove))
+            && !self.avoid_moves.contains(cmove)
+            && self.mate_in.is_none_or(|n| score > MATE_BOUND && (MATE_VALUE - score) / 2 < n)
+    }
+}
+
+pub enum EpdLimit {
+    Time(i64),
+    Depth(i8),
+}
+
+///Parses a line like `2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";`
+pub fn parse_epd(line: &str) -> Result<EpdEntry, String> {
+    //Board, side to move, castling and en passant, without the move counters of a FEN
+    let mut rest = line.trim();
+    let mut fields = vec![];
+    for _ in 0..4 {
+        let (field, next) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        fields.push(field);
+        rest = next.trim_start();
+    }
+    let fen = format!("{} 0 1", fields.join(" "));
+    let mut game = Game::new_from_fen(&fen).ok_or(format!("invalid position '{}'", fields.join(" ")))?;
+
+    let mut entry = EpdEntry { game, id: String::new(), best_moves: vec![], avoid_moves: vec![], centipawns: None, mate_in: None };
+
+    for (opcode, operands) in split_operations(rest) {
+        let moves = |game: &mut Game| operands.iter()
+            .map(|san| game.parse_san(san).ok_or(format!("illegal move '{}' in {}", san, fen)))
+            .collect::<Result<Vec<Move>, String>>();
+
+        match opcode.as_str() {
+            "bm" => entry.best_moves = moves(&mut game)?,
+            "am" => entry.avoid_moves = moves(&mut game)?,
+            "id" => entry.id = operands.join(" "),
+            "ce" => entry.centipawns = operands.first().and_then(|ce| ce.parse().ok()),
+            "dm" => entry.mate_in = operands.first().and_then(|dm| dm.parse().ok()),
+            _ => {}
+        }
+    }
+
+    Ok(entry)
+}
+
+///Splits `bm Qg6; id "WAC.001";` into opcodes and their operands. Quoted operands may contain spaces and semicolons
+fn split_operations(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut operations = vec![];
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' | ' ' | '\t' if !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if c == ';' && !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            },
+            _ => word.push(c)
+        }
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+
+    operations
+}
+
+///Searches every position of an EPD file and reports whether the engine found the solution, and how quickly
+pub fn run_epd(path: &str, limit: EpdLimit, evaluator: &mut dyn Evaluator) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => { println!(" Could not read '{}': {}", path, e); return; }
+    };
+
+    let (depth, max_time) = match limit {
+        EpdLimit::Time(ms) => (-1, ms),
+        EpdLimit::Depth(depth) => (depth, -1)
+    };
+
+    let start = SystemTime::now();
+    let mut tt = TranspositionTable::new();
+    let (mut solved, mut failed, mut unscored) = (0, 0, 0);
+    let mut solution_time = 0;
+
+    for (number, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty() && !l.starts_with('#')) {
+        let mut entry = match parse_epd(line) {
+            Ok(entry) => entry,
+            Err(e) => { println!(" Skipping line {}: {}", number + 1, e); continue; }
+        };
+        let id = if entry.id.is_empty() { format!("line {}", number + 1) } else { entry.id.clone() };
+
+        tt.clear();
+        let mut rep_table = RepetitionTable::new();
+        let mut envir = SearchEnv::new(max_time, None, &mut tt, &mut rep_table, evaluator);
+        envir.silent = true;
+        let result = iterative_deepening(&mut entry.game, depth, &mut envir);
+
+        let white_score = if entry.game.active_player == Color::White { result.score } else { -result.score };
+        let found = format!("{} ({})", entry.game.move_to_san(&result.best_move), eval_comment(white_score, result.depth));
+        let expected = entry.game.line_to_san(&entry.best_moves).iter().map(|m| format!("bm {}", m))
+            .chain(entry.game.line_to_san(&entry.avoid_moves).iter().map(|m| format!("am {}", m)))
+            .chain(entry.mate_in.map(|n| format!("dm {}", n)))
+            .chain(entry.centipawns.map(|ce| format!("ce {}", ce)))
+            .collect::<Vec<_>>().join(" ");
+
+        if !entry.is_scored() {
+            unscored += 1;
+            println!("{}", format!(" {:<16} -       found {}  {}", id, found, expected).trim_end());
+        }
+        else if entry.is_solution(&result.best_move, result.score) {
+            //Solved from the first iteration after which every iteration found a solution
+            let first = envir.iterations.iter().rposition(|(m, score, _)| !entry.is_solution(m, *score)).map_or(0, |i| i + 1);
+            let time = envir.iterations.get(first).map_or(0, |(_, _, ms)| *ms);
+            solution_time += time;
+            solved += 1;
+            println!(" {:<16} solved  found {}  {}  in {}ms", id, found, expected, time);
+        }
+        else {
+            failed += 1;
+            println!(" {:<16} failed  found {}  {}", id, found, expected);
+        }
+    }
+
+    println!("\n Solved {} of {} positions, failed {}, unscored {}", solved, solved + failed, failed, unscored);
+    println!(" Time to solution: {}ms, total time: {}ms", solution_time, start.elapsed().unwrap().as_millis());
+}
+
+#[cfg(test)]
+mod epd_tests {
+    use super::*;
+
+    #[test]
+    pub fn parses_operations() {
+        let entry = parse_epd("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";").unwrap();
+        assert_eq!(entry.id, "WAC.001");
+        assert_eq!(entry.best_moves.iter().map(|m| m.to_uci()).collect::<Vec<_>>(), vec!["g3g6"]);
+        assert!(entry.avoid_moves.is_empty());
+
+        let entry = parse_epd("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - am Ba6 Nxe5; ce 35; dm 7; id \"a; b\"").unwrap();
+        assert_eq!(entry.id, "a; b");
+        assert_eq!(entry.avoid_moves.len(), 2);
+        assert_eq!((entry.centipawns, entry.mate_in), (Some(35), Some(7)));
+
+        assert!(parse_epd("8/8/8/8/8/8/8/8 w - - bm e4;").is_err());
+        assert!(parse_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Qd1;").is_err());
+    }
+
+    #[test]
+    pub fn scores_solutions() {
+        let mut entry = parse_epd("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - bm Ra8; dm 1;").unwrap();
+        let ra8 = entry.best_moves[0];
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let mut envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+        envir.silent = true;
+
+        let result = iterative_deepening(&mut entry.game, 3, &mut envir);
+        assert!(entry.is_solution(&result.best_move, result.score));
+        assert!(!entry.is_solution(&ra8, 0));
+        assert_eq!(envir.iterations.len(), 3);
+    }
+}
@@ -56,6 +56,19 @@ pub const ROOK_SCORES: [i32; 64] =
 
 ];
 
+// queen positional score, (middlegame, endgame)
+pub const QUEEN_SCORES: [(i32, i32); 64] = 
+[
+    (-10, -10), ( -5,  -5), ( -5,  -5), (  0,  -5), (  0,  -5), ( -5,  -5), ( -5,  -5), (-10, -10),
+    ( -5,  -5), (  0,   0), (  0,   5), (  0,   5), (  0,   5), (  0,   5), (  0,   0), ( -5,  -5),
+    ( -5,  -5), (  0,   5), (  5,  10), (  5,  10), (  5,  10), (  5,  10), (  0,   5), ( -5,  -5),
+    ( -5,  -5), (  0,   5), (  5,  10), (  5,  15), (  5,  15), (  5,  10), (  0,   5), ( -5,  -5),
+    ( -5,  -5), (  0,   5), (  5,  10), (  5,  15), (  5,  15), (  5,  10), (  0,   5), ( -5,  -5),
+    ( -5,  -5), (  5,   5), (  5,  10), (  5,  10), (  5,  10), (  5,  10), (  0,   5), ( -5,  -5),
+    ( -5,  -5), (  0,   0), (  5,   5), (  0,   5), (  0,   5), (  0,   5), (  0,   0), ( -5,  -5),
+    (-10, -10), ( -5,  -5), ( -5,  -5), (  0,  -5), (  0,  -5), ( -5,  -5), ( -5,  -5), (-10, -10)
+];
+
 // king positional score
 pub const KING_SCORES: [i32; 64] = 
 [
@@ -100,7 +113,7 @@ pub const MVV_LVA: [[i32; 12]; 12] = [
 ];
 
 
-const FILE_MASKS: [u64; 64] = generate_file_masks();
+pub const FILE_MASKS: [u64; 64] = generate_file_masks();
 const RANK_MASKS: [u64; 64] = generate_rank_masks();
 const ISOLATED_MASKS: [u64; 64] = generate_isolated_pawn_masks();
 
@@ -111,221 +124,808 @@ const STACKED_PAWN_PENALTY: i32 = -10;
 const ISOLATED_PAWN_PENALTY: i32 = -10;
 const PASSED_WHITE_PAWN_BONUS: [i32; 8] = [ 0, 10, 30, 50, 75, 100, 150, 200 ];
 const PASSED_BLACK_PAWN_BONUS: [i32; 8] = [ 200, 150, 100, 75, 50, 30, 10, 0 ]; 
+const BACKWARD_PAWN_PENALTY: i32 = -8;
+//Indexed by the rank relative to the pawn's own side
+const CONNECTED_PAWN_BONUS: [i32; 8] = [ 0, 3, 5, 8, 15, 25, 40, 0 ];
+const CANDIDATE_PASSER_BONUS: [i32; 8] = [ 0, 5, 10, 15, 25, 40, 0, 0 ];
+const PROTECTED_PASSER_BONUS: [i32; 8] = [ 0, 0, 5, 10, 15, 25, 35, 0 ];
+const BLOCKADED_PASSER_PENALTY: [i32; 8] = [ 0, 0, -5, -10, -20, -30, -50, 0 ];
+const KING_PASSER_DISTANCE_WEIGHT: [i32; 8] = [ 0, 0, 0, 0, 1, 2, 3, 0 ];
+const UNSTOPPABLE_PASSER_BONUS: i32 = 600;
+//Non-pawn material of both sides at or below which king distance to passers is scored
+const ENDGAME_MATERIAL: i32 = 1300;
 const SEMI_OPEN_FILE_SCORE: i32 = 10;
 const OPEN_FILE_SCORE: i32 = 15;
-const PROTECTED_KING_BONUS: i32 = 5;
 
-/*const KNIGHT_UNIT: u32 = 4;
-const BISHOP_UNIT: u32 = 6;
-const ROOK_UNIT: u32 = 7;
-const QUEEN_UNIT: u32 = 13;
+//Variants
+//King of the Hill bonus by the king's distance to the nearest center square
+const HILL_DISTANCE_BONUS: [i32; 4] = [ 0, 120, 50, 15 ];
+//Three-check bonus by the number of checks given
+const CHECKS_GIVEN_BONUS: [i32; 4] = [ 0, 150, 400, 0 ];
+//Atomic penalty per piece next to the own king, as capturing it explodes the king
+const ATOMIC_KING_NEIGHBOUR_PENALTY: i32 = -20;
+//Crazyhouse values of the pieces in hand, by piece % 6. Pawns can be dropped anywhere, so they are worth more than on the board
+const POCKET_VALUES: [i32; 5] = [ 150, 300, 330, 450, 900 ];
+//Crazyhouse penalty per empty square next to the king and piece in the opponent's pocket, counting at most 5 pieces
+const POCKET_KING_DANGER: i32 = -4;
+//Horde penalty by the pieces left to the side without a king, which loses with the last one
+const HORDE_PIECES_LEFT_PENALTY: [i32; 9] = [ 0, -300, -180, -110, -70, -40, -20, -10, 0 ];
+
+//King safety
+const PAWN_SHIELD_BONUS: i32 = 10;
+const PAWN_STORM_PENALTY: i32 = -8;
+//Danger units per piece attacking the king zone, indexed by piece % 6
+const KING_ATTACK_WEIGHTS: [i32; 6] = [ 0, 2, 2, 3, 5, 0 ];
+//Danger units for having a safe check with the piece, indexed by piece % 6
+const SAFE_CHECK_WEIGHTS: [i32; 6] = [ 0, 3, 2, 4, 6, 0 ];
+const KING_DANGER: [i32; 100] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+     68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500
+];
+
+//Mobility by the number of reachable squares not occupied by own pieces or attacked by enemy pawns, as (middlegame, endgame)
+const KNIGHT_MOBILITY: [(i32, i32); 9] = [
+    (-30, -40), (-20, -28), (-5, -15), (0, -5), (5, 5), (10, 8), (15, 12), (20, 14), (24, 16)
+];
+const BISHOP_MOBILITY: [(i32, i32); 14] = [
+    (-25, -35), (-12, -18), (0, -8), (6, 0), (12, 6), (18, 12), (22, 18),
+    (25, 22), (28, 25), (30, 28), (33, 30), (36, 32), (38, 34), (40, 35)
+];
+const ROOK_MOBILITY: [(i32, i32); 15] = [
+    (-20, -40), (-12, -20), (-6, -8), (-3, 0), (0, 8), (3, 15), (6, 22), (9, 28),
+    (12, 34), (14, 40), (16, 44), (18, 48), (20, 52), (22, 55), (24, 58)
+];
+const QUEEN_MOBILITY: [(i32, i32); 28] = [
+    (-15, -25), (-10, -18), (-6, -12), (-3, -6), (0, -2), (2, 2), (4, 6), (6, 10), (8, 14), (10, 17),
+    (12, 20), (13, 23), (14, 26), (15, 29), (16, 31), (17, 33), (18, 35), (19, 37), (20, 39), (21, 41),
+    (22, 43), (23, 44), (24, 45), (25, 46), (26, 47), (27, 48), (28, 49), (30, 50)
+];
+
+//Game phase, from 24 with all pieces on the board down to 0 with only kings and pawns. Indexed by piece % 6
+const PHASE_WEIGHTS: [i32; 6] = [ 0, 1, 1, 2, 4, 0 ];
+const MAX_PHASE: i32 = 24;
+
+
+///An evaluation backend used by the search. Scores are from the perspective of the side to move
+pub trait Evaluator {
+    fn evaluate(&mut self, game: &Game) -> i32;
+
+    ///Forgets everything cached between positions
+    fn clear(&mut self) {}
+
+    ///Called with the root position before a search or evaluation
+    fn prepare(&mut self, _game: &mut Game) {}
+
+    ///Called after every move of the search, with the position it was made in
+    fn update(&mut self, _before: &Game, _after: &mut Game) {}
+}
+
+///The hand-crafted evaluation, with pawn structure cached in a pawn hash table
+pub struct ClassicEvaluator {
+    pawn_table: PawnHashTable,
+}
 
-const KNIGHT_MOB: i32 = 4;
-const BISHOP_MOB: i32 = 5;
-const ROOK_MOB: i32 = 3;
-const QUEEN_MOB: f32 = 1.5;*/
+impl ClassicEvaluator {
+    pub fn new() -> Self {
+        Self { pawn_table: PawnHashTable::new() }
+    }
+}
 
+impl Evaluator for ClassicEvaluator {
+    fn evaluate(&mut self, game: &Game) -> i32 {
+        evaluate_cached(game, &mut self.pawn_table)
+    }
+
+    fn clear(&mut self) {
+        self.pawn_table.clear();
+    }
+}
 
 pub fn evaluate(game: &Game) -> i32 {
-    let mut score: i32 = 0;
+    evaluate_traced(game, &mut ())
+}
+
+///Evaluates the position while reporting every term to the given tracer. Pass `&mut ()` to skip tracing
+#[inline(always)]
+pub fn evaluate_traced<T: EvalTracer>(game: &Game, tracer: &mut T) -> i32 {
+    let pawns = evaluate_pawns(game, tracer);
+    evaluate_with_pawns(game, &pawns, tracer)
+}
+
+///Evaluates the position, looking up the pawn structure in the pawn hash table
+#[inline(always)]
+pub fn evaluate_cached(game: &Game, pawn_table: &mut PawnHashTable) -> i32 {
+    let pawns = match pawn_table.probe(game.pawn_hash) {
+        Some(entry) => entry,
+        None => {
+            let entry = evaluate_pawns(game, &mut ());
+            pawn_table.record(entry);
+            entry
+        }
+    };
+    evaluate_with_pawns(game, &pawns, &mut ())
+}
+
+///Pawn structure terms. They only depend on the pawns, so the result can be stored in the pawn hash table
+pub fn evaluate_pawns<T: EvalTracer>(game: &Game, tracer: &mut T) -> PawnEntry {
+    let mut acc = EvalAccumulator { score: 0, tracer };
 
     let mut stacked_pawns;
+    let mut passed = [Bitboard::new(); 2];
 
-    for bb in 0..12 {
+    let white_pawns = game.get_piece_bitboard(Piece::WhitePawn).to_u64();
+    let black_pawns = game.get_piece_bitboard(Piece::BlackPawn).to_u64();
+    let white_attacks = pawn_attacks(white_pawns, Color::White);
+    let black_attacks = pawn_attacks(black_pawns, Color::Black);
+
+    for bb in [Piece::WhitePawn as usize, Piece::BlackPawn as usize] {
         let mut board = game.bitboards[bb];
         while !board.is_empty() {
             let square = board.extract_bit();
-            score += MATERIAL_WEIGHTS[bb];
             match bb {
                 //White pawns
                 0  => {
-                    score += PAWN_SCORES[square as usize];
+                    acc.add(EvalTerm::PieceSquare, Color::White, square, PAWN_SCORES[square as usize]);
+
+                    let relative_rank = LOOKUP_RANK[square as usize];
+                    //Own pawns on the adjacent files, level with or behind this one
+                    let supporters = white_pawns & ISOLATED_MASKS[square as usize] & !WHITE_PASSED_PAWN_MASKS[square as usize];
 
                     //Stacked pawn penalty
                     stacked_pawns = game.get_piece_bitboard(Piece::WhitePawn)
                                         .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
                                         .pop_count();
                     if stacked_pawns > 1 {
-                        score += stacked_pawns as i32 * STACKED_PAWN_PENALTY;
+                        acc.add(EvalTerm::StackedPawns, Color::White, square, stacked_pawns as i32 * STACKED_PAWN_PENALTY);
                     }
 
                     //Isolated pawn penalty
                     if game.get_piece_bitboard(Piece::WhitePawn)
                             .and(Bitboard::from_u64(ISOLATED_MASKS[square as usize]))
                             .is_empty() {
-                        score += ISOLATED_PAWN_PENALTY;
+                        acc.add(EvalTerm::IsolatedPawns, Color::White, square, ISOLATED_PAWN_PENALTY);
                     }
 
-                    //Passed pawn penalty
+                    //Passed pawn bonus
                     if game.get_piece_bitboard(Piece::BlackPawn)
                            .and(Bitboard::from_u64(WHITE_PASSED_PAWN_MASKS[square as usize]))
                            .is_empty() {
-                        score += PASSED_WHITE_PAWN_BONUS[LOOKUP_RANK[square as usize]];
+                        acc.add(EvalTerm::PassedPawns, Color::White, square, PASSED_WHITE_PAWN_BONUS[LOOKUP_RANK[square as usize]]);
+                        passed[0].set_bit(square);
+
+                        //Protected passer bonus
+                        if white_attacks & (1 << square) != 0 {
+                            acc.add(EvalTerm::PassedPawns, Color::White, square, PROTECTED_PASSER_BONUS[relative_rank]);
+                        }
+                    }
+                    //Candidate passer bonus, when nothing blocks the file and the helpers outnumber the sentries
+                    else if black_pawns & FILE_MASKS[square as usize] & WHITE_PASSED_PAWN_MASKS[square as usize] == 0
+                            && supporters.count_ones() >= (black_pawns & WHITE_PASSED_PAWN_MASKS[square as usize]).count_ones() {
+                        acc.add(EvalTerm::CandidatePawns, Color::White, square, CANDIDATE_PASSER_BONUS[relative_rank]);
+                    }
+
+                    //Phalanx and connected pawn bonus
+                    if white_pawns & ISOLATED_MASKS[square as usize] & RANK_MASKS[square as usize] != 0 {
+                        acc.add(EvalTerm::ConnectedPawns, Color::White, square, CONNECTED_PAWN_BONUS[relative_rank]);
+                    }
+                    if white_attacks & (1 << square) != 0 {
+                        acc.add(EvalTerm::ConnectedPawns, Color::White, square, CONNECTED_PAWN_BONUS[relative_rank]);
+                    }
+
+                    //Backward pawn penalty, when no pawn can come to support it and an enemy pawn guards the stop square
+                    if supporters == 0 && white_pawns & ISOLATED_MASKS[square as usize] != 0 && black_attacks & (1 << (square - 8)) != 0 {
+                        acc.add(EvalTerm::BackwardPawns, Color::White, square, BACKWARD_PAWN_PENALTY);
+                    }
+                },
+                //Black pawns
+                6  => {
+                    acc.add(EvalTerm::PieceSquare, Color::Black, square, PAWN_SCORES[MIRRORED[square as usize]]);
+
+                    let relative_rank = 7 - LOOKUP_RANK[square as usize];
+                    //Own pawns on the adjacent files, level with or behind this one
+                    let supporters = black_pawns & ISOLATED_MASKS[square as usize] & !BLACK_PASSED_PAWN_MASKS[square as usize];
+                    
+                    //Stacked pawn penalty
+                    stacked_pawns = game.get_piece_bitboard(Piece::BlackPawn)
+                                        .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
+                                        .pop_count();
+                    if stacked_pawns > 1 {
+                        acc.add(EvalTerm::StackedPawns, Color::Black, square, stacked_pawns as i32 * STACKED_PAWN_PENALTY);
+                    }
+
+                    //Isolated pawn penalty
+                    if game.get_piece_bitboard(Piece::BlackPawn)
+                           .and(Bitboard::from_u64(ISOLATED_MASKS[square as usize]))
+                           .is_empty() {
+                        acc.add(EvalTerm::IsolatedPawns, Color::Black, square, ISOLATED_PAWN_PENALTY);
+                    }
+
+                    //Passed pawn bonus
+                    if game.get_piece_bitboard(Piece::WhitePawn)
+                           .and(Bitboard::from_u64(BLACK_PASSED_PAWN_MASKS[square as usize]))
+                           .is_empty() {
+                        acc.add(EvalTerm::PassedPawns, Color::Black, square, PASSED_BLACK_PAWN_BONUS[LOOKUP_RANK[square as usize]]);
+                        passed[1].set_bit(square);
+
+                        //Protected passer bonus
+                        if black_attacks & (1 << square) != 0 {
+                            acc.add(EvalTerm::PassedPawns, Color::Black, square, PROTECTED_PASSER_BONUS[relative_rank]);
+                        }
+                    }
+                    //Candidate passer bonus, when nothing blocks the file and the helpers outnumber the sentries
+                    else if white_pawns & FILE_MASKS[square as usize] & BLACK_PASSED_PAWN_MASKS[square as usize] == 0
+                            && supporters.count_ones() >= (white_pawns & BLACK_PASSED_PAWN_MASKS[square as usize]).count_ones() {
+                        acc.add(EvalTerm::CandidatePawns, Color::Black, square, CANDIDATE_PASSER_BONUS[relative_rank]);
+                    }
+
+                    //Phalanx and connected pawn bonus
+                    if black_pawns & ISOLATED_MASKS[square as usize] & RANK_MASKS[square as usize] != 0 {
+                        acc.add(EvalTerm::ConnectedPawns, Color::Black, square, CONNECTED_PAWN_BONUS[relative_rank]);
+                    }
+                    if black_attacks & (1 << square) != 0 {
+                        acc.add(EvalTerm::ConnectedPawns, Color::Black, square, CONNECTED_PAWN_BONUS[relative_rank]);
+                    }
+
+                    //Backward pawn penalty, when no pawn can come to support it and an enemy pawn guards the stop square
+                    if supporters == 0 && black_pawns & ISOLATED_MASKS[square as usize] != 0 && white_attacks & (1 << (square + 8)) != 0 {
+                        acc.add(EvalTerm::BackwardPawns, Color::Black, square, BACKWARD_PAWN_PENALTY);
                     }
                 },
+                _ => unreachable!()
+            };
+        }
+    }
+
+    PawnEntry {
+        key: game.pawn_hash,
+        score: acc.score,
+        passed,
+    }
+}
+
+///Everything but the pawn structure, which is given by the pawn entry
+#[inline(always)]
+fn evaluate_with_pawns<T: EvalTracer>(game: &Game, pawns: &PawnEntry, tracer: &mut T) -> i32 {
+    let mut acc = EvalAccumulator { score: pawns.score, tracer };
+    let mut attack_info = AttackInfo::new(game);
+
+    let phase = game_phase(game);
+    let mobility_area = [
+        !game.white_occupancies.to_u64() & !attack_info.attacked_by[1][0],
+        !game.black_occupancies.to_u64() & !attack_info.attacked_by[0][0],
+    ];
+
+    for bb in 0..12 {
+        let mut board = game.bitboards[bb];
+        while !board.is_empty() {
+            let square = board.extract_bit();
+            let color = if bb < 6 { Color::White } else { Color::Black };
+            acc.add(EvalTerm::Material, color, square, MATERIAL_WEIGHTS[bb % 6]);
+            match bb {
+                //Pawns are evaluated in evaluate_pawns
+                0 | 6 => {},
                 //White knight
                 1  => {
-                    score += KNIGHT_SCORES[square as usize];
+                    acc.add(EvalTerm::PieceSquare, Color::White, square, KNIGHT_SCORES[square as usize]);
 
                     //Mobility
-                    //score += (get_knight_attack_table(square).pop_count() - KNIGHT_UNIT) as i32 * KNIGHT_MOB;
-                    score += get_knight_attack_table(square).pop_count() as i32;
+                    let attacks = get_knight_attack_table(square).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::White, square, taper(KNIGHT_MOBILITY[(attacks & mobility_area[0]).count_ones() as usize], phase));
+                    attack_info.add(0, 1, attacks);
                 },
                 //White bishops
                 2  => {
-                    score += BISHOP_SCORES[square as usize];
+                    acc.add(EvalTerm::PieceSquare, Color::White, square, BISHOP_SCORES[square as usize]);
 
                     //Mobility
-                    //score += (get_bishop_attack_table(square, game.all_occupancies).pop_count() - BISHOP_UNIT) as i32 * BISHOP_MOB;
-                    score += (get_bishop_attack_table(square, game.all_occupancies).pop_count()) as i32;
-
+                    let attacks = get_bishop_attack_table(square, game.all_occupancies).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::White, square, taper(BISHOP_MOBILITY[(attacks & mobility_area[0]).count_ones() as usize], phase));
+                    attack_info.add(0, 2, attacks);
                 },
                 //White Rooks
                 3  => {
-                    score += ROOK_SCORES[square as usize];
+                    acc.add(EvalTerm::PieceSquare, Color::White, square, ROOK_SCORES[square as usize]);
 
                     //Semi open file bonus
                     if game.get_piece_bitboard(Piece::WhitePawn)
                            .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
                            .is_empty() {
-                        score += SEMI_OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::RookFiles, Color::White, square, SEMI_OPEN_FILE_SCORE);
                     }
 
                     //Open file bonus
                     if (game.get_piece_bitboard(Piece::WhitePawn)
                             .or(game.get_piece_bitboard(Piece::BlackPawn)))
                                 .and(Bitboard::from_u64(FILE_MASKS[square as usize])).is_empty() {
-                        score += OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::RookFiles, Color::White, square, OPEN_FILE_SCORE);
                     }
 
                     //Mobility
-                    //score += (get_rook_attack_table(square, game.all_occupancies).pop_count() - ROOK_UNIT) as i32 * ROOK_MOB;
-                    score += get_rook_attack_table(square, game.all_occupancies).pop_count() as i32;
+                    let attacks = get_rook_attack_table(square, game.all_occupancies).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::White, square, taper(ROOK_MOBILITY[(attacks & mobility_area[0]).count_ones() as usize], phase));
+                    attack_info.add(0, 3, attacks);
                 },
                 //White queen
                 4 => {
+                    acc.add(EvalTerm::PieceSquare, Color::White, square, taper(QUEEN_SCORES[square as usize], phase));
+
                     //Mobility
-                    //score += ((get_queen_attack_table(square, game.all_occupancies).pop_count() - QUEEN_UNIT) as f32 * QUEEN_MOB) as i32;
-                    score += get_queen_attack_table(square, game.all_occupancies).pop_count() as i32;
+                    let attacks = get_queen_attack_table(square, game.all_occupancies).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::White, square, taper(QUEEN_MOBILITY[(attacks & mobility_area[0]).count_ones() as usize], phase));
+                    attack_info.add(0, 4, attacks);
                 },
                 //White king
                 5  => {
-                    score += KING_SCORES[square as usize];
+                    acc.add(EvalTerm::PieceSquare, Color::White, square, KING_SCORES[square as usize]);
 
                     //Semi open file penalty
                     if game.get_piece_bitboard(Piece::WhitePawn)
                            .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
                            .is_empty() {
-                        score -= SEMI_OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::KingSafety, Color::White, square, -SEMI_OPEN_FILE_SCORE);
                     }
 
                     //Open file penalty
                     if (game.get_piece_bitboard(Piece::WhitePawn)
                             .or(game.get_piece_bitboard(Piece::BlackPawn)))
                                 .and(Bitboard::from_u64(FILE_MASKS[square as usize])).is_empty() {
-                        score -= OPEN_FILE_SCORE;
-                    }
-
-                    //King safety
-                    score += get_king_attack_table(square).and(game.white_occupancies).pop_count() as i32 * PROTECTED_KING_BONUS;
-                },
-                //Black pawns
-                6  => {
-                    score -= PAWN_SCORES[MIRRORED[square as usize]];
-                    
-                    //Stacked pawn penalty
-                    stacked_pawns = game.get_piece_bitboard(Piece::BlackPawn)
-                                        .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
-                                        .pop_count();
-                    if stacked_pawns > 1 {
-                        score -= stacked_pawns as i32 * STACKED_PAWN_PENALTY;
-                    }
-
-                    //Isolated pawn penalty
-                    if game.get_piece_bitboard(Piece::BlackPawn)
-                           .and(Bitboard::from_u64(ISOLATED_MASKS[square as usize]))
-                           .is_empty() {
-                        score -= ISOLATED_PAWN_PENALTY;
-                    }
-
-                    //Passed pawn penalty
-                    if game.get_piece_bitboard(Piece::WhitePawn)
-                           .and(Bitboard::from_u64(BLACK_PASSED_PAWN_MASKS[square as usize]))
-                           .is_empty() {
-                        score -= PASSED_BLACK_PAWN_BONUS[LOOKUP_RANK[square as usize]];
+                        acc.add(EvalTerm::KingSafety, Color::White, square, -OPEN_FILE_SCORE);
                     }
                 },
                 //Black knight
                 7  => {
-                    score -= KNIGHT_SCORES[MIRRORED[square as usize]];
+                    acc.add(EvalTerm::PieceSquare, Color::Black, square, KNIGHT_SCORES[MIRRORED[square as usize]]);
 
                     //Mobility
-                    //score -= (get_knight_attack_table(square).pop_count() - KNIGHT_UNIT) as i32 * KNIGHT_MOB;
-                    score -= get_knight_attack_table(square).pop_count() as i32;
+                    let attacks = get_knight_attack_table(square).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::Black, square, taper(KNIGHT_MOBILITY[(attacks & mobility_area[1]).count_ones() as usize], phase));
+                    attack_info.add(1, 1, attacks);
                 },
                 //Black bishop
                 8  => {
-                    score -= BISHOP_SCORES[MIRRORED[square as usize]];
+                    acc.add(EvalTerm::PieceSquare, Color::Black, square, BISHOP_SCORES[MIRRORED[square as usize]]);
 
                     //Mobility
-                    //score -= (get_bishop_attack_table(square, game.all_occupancies).pop_count() - BISHOP_UNIT) as i32 * BISHOP_MOB;
-                    score -= get_bishop_attack_table(square, game.all_occupancies).pop_count() as i32;
+                    let attacks = get_bishop_attack_table(square, game.all_occupancies).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::Black, square, taper(BISHOP_MOBILITY[(attacks & mobility_area[1]).count_ones() as usize], phase));
+                    attack_info.add(1, 2, attacks);
                 },
                 //Black rooks
                 9  => {
-                    score -= ROOK_SCORES[MIRRORED[square as usize]];
+                    acc.add(EvalTerm::PieceSquare, Color::Black, square, ROOK_SCORES[MIRRORED[square as usize]]);
 
                     //Semi open file bonus
                     if game.get_piece_bitboard(Piece::BlackPawn)
                            .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
                            .is_empty() {
-                        score -= SEMI_OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::RookFiles, Color::Black, square, SEMI_OPEN_FILE_SCORE);
                     }
 
                     //Open file bonus
                     if (game.get_piece_bitboard(Piece::BlackPawn)
                             .or(game.get_piece_bitboard(Piece::WhitePawn)))
                                 .and(Bitboard::from_u64(FILE_MASKS[square as usize])).is_empty() {
-                        score -= OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::RookFiles, Color::Black, square, OPEN_FILE_SCORE);
                     }
 
                     //Mobility
-                    //score -= (get_rook_attack_table(square, game.all_occupancies).pop_count() - ROOK_UNIT) as i32 * ROOK_MOB;
-                    score -= get_rook_attack_table(square, game.all_occupancies).pop_count() as i32;
+                    let attacks = get_rook_attack_table(square, game.all_occupancies).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::Black, square, taper(ROOK_MOBILITY[(attacks & mobility_area[1]).count_ones() as usize], phase));
+                    attack_info.add(1, 3, attacks);
                 },
                 //Black queen
                 10 => {
+                    acc.add(EvalTerm::PieceSquare, Color::Black, square, taper(QUEEN_SCORES[MIRRORED[square as usize]], phase));
+
                     //Mobility
-                    //score -= ((get_queen_attack_table(square, game.all_occupancies).pop_count() - QUEEN_UNIT) as f32 * QUEEN_MOB) as i32;
-                    score -= get_queen_attack_table(square, game.all_occupancies).pop_count() as i32;
+                    let attacks = get_queen_attack_table(square, game.all_occupancies).to_u64();
+                    acc.add(EvalTerm::Mobility, Color::Black, square, taper(QUEEN_MOBILITY[(attacks & mobility_area[1]).count_ones() as usize], phase));
+                    attack_info.add(1, 4, attacks);
                 }
                 //Black king
                 11 => {
-                    score -= KING_SCORES[MIRRORED[square as usize]];
+                    acc.add(EvalTerm::PieceSquare, Color::Black, square, KING_SCORES[MIRRORED[square as usize]]);
 
                     //Semi open file penalty
                     if game.get_piece_bitboard(Piece::BlackPawn)
                            .and(Bitboard::from_u64(FILE_MASKS[square as usize]))
                            .is_empty() {
-                        score += SEMI_OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::KingSafety, Color::Black, square, -SEMI_OPEN_FILE_SCORE);
                     }
 
                     //Open file penalty
                     if (game.get_piece_bitboard(Piece::BlackPawn)
                             .or(game.get_piece_bitboard(Piece::WhitePawn)))
                                 .and(Bitboard::from_u64(FILE_MASKS[square as usize])).is_empty() {
-                        score += OPEN_FILE_SCORE;
+                        acc.add(EvalTerm::KingSafety, Color::Black, square, -OPEN_FILE_SCORE);
                     }
-
-                    //King safety
-                    score -= get_king_attack_table(square).and(game.black_occupancies).pop_count() as i32 * PROTECTED_KING_BONUS;
                 },
                 _ => unreachable!()
             };
         }
     }
 
-    if game.active_player == Color::White { score } else { -score }
+    evaluate_passers(game, pawns.passed[0], Color::White, &mut acc);
+    evaluate_passers(game, pawns.passed[1], Color::Black, &mut acc);
+
+    evaluate_king_safety(game, Color::White, &attack_info, &mut acc);
+    evaluate_king_safety(game, Color::Black, &attack_info, &mut acc);
+
+    if game.variant != Variant::Standard {
+        evaluate_variant(game, Color::White, &mut acc);
+        evaluate_variant(game, Color::Black, &mut acc);
+    }
+
+    //Known endgames replace the evaluation, and drawish material scales it down. Their rules don't hold in the variants
+    let score = match probe_endgame(game) {
+        _ if game.variant != Variant::Standard => acc.score,
+        Some((evaluator, strong)) => if strong == Color::White { evaluator(game, strong) } else { -evaluator(game, strong) },
+        None => acc.score * scale_factor(game, if acc.score >= 0 { Color::White } else { Color::Black }) / SCALE_NORMAL
+    };
+    if score != acc.score {
+        acc.add(EvalTerm::Endgame, Color::White, 0, score - acc.score);
+    }
+
+    if game.active_player == Color::White { acc.score } else { -acc.score }
+}
+
+///Progress towards the win condition of the variant
+#[inline(always)]
+fn evaluate_variant<T: EvalTracer>(game: &Game, color: Color, acc: &mut EvalAccumulator<T>) {
+    let (own, opponent) = if color == Color::White { (0, 6) } else { (6, 0) };
+    let king = game.bitboards[own + 5].least_significant();
+
+    match game.variant {
+        Variant::KingOfTheHill => {
+            let distance = [Square::d5, Square::e5, Square::d4, Square::e4].iter().map(|sq| square_distance(king, *sq as u8)).min().unwrap();
+            acc.add(EvalTerm::Variant, color, king, HILL_DISTANCE_BONUS[distance.min(3) as usize]);
+        },
+        Variant::ThreeCheck => {
+            let side = if color == Color::White { 0 } else { 1 };
+            acc.add(EvalTerm::Variant, color, king, CHECKS_GIVEN_BONUS[game.checks_given[side].min(3) as usize]);
+        },
+        Variant::Atomic => {
+            let neighbours = get_king_attack_table(king).to_u64() & game.all_occupancies.to_u64() & !game.bitboards[opponent + 5].to_u64();
+            acc.add(EvalTerm::Variant, color, king, neighbours.count_ones() as i32 * ATOMIC_KING_NEIGHBOUR_PENALTY);
+        },
+        Variant::Crazyhouse => {
+            let side = if color == Color::White { 0 } else { 1 };
+            let in_hand: i32 = (0..5).map(|p| game.pockets[side][p] as i32 * POCKET_VALUES[p]).sum();
+            acc.add(EvalTerm::Variant, color, king, in_hand);
+
+            //The opponent's pieces in hand can be dropped on the empty squares around the king
+            let drops = game.pockets[1 - side].iter().map(|c| *c as i32).sum::<i32>().min(5);
+            let open = (get_king_attack_table(king).to_u64() & !game.all_occupancies.to_u64()).count_ones() as i32;
+            acc.add(EvalTerm::Variant, color, king, drops * open * POCKET_KING_DANGER);
+        },
+        Variant::Horde => {
+            let pieces = if color == Color::White { game.white_occupancies } else { game.black_occupancies };
+            if game.bitboards[own + 5].is_empty() && !pieces.is_empty() {
+                //The material counts the kings, which only cancel out with both on the board
+                acc.add(EvalTerm::Material, color, pieces.least_significant(), MATERIAL_WEIGHTS[5]);
+                acc.add(EvalTerm::Variant, color, pieces.least_significant(), HORDE_PIECES_LEFT_PENALTY[pieces.to_u64().count_ones().min(8) as usize]);
+            }
+        },
+        Variant::Standard => {}
+    }
+}
+
+///Passed pawn terms that depend on more than the pawns: blockades, king distance and the rule of the square
+#[inline(always)]
+fn evaluate_passers<T: EvalTracer>(game: &Game, mut passed: Bitboard, color: Color, acc: &mut EvalAccumulator<T>) {
+    if passed.is_empty() {
+        return;
+    }
+
+    let (enemy_occupancies, own_king, enemy_king) = if color == Color::White {
+        (game.black_occupancies, game.get_piece_bitboard(Piece::WhiteKing), game.get_piece_bitboard(Piece::BlackKing))
+    } else {
+        (game.white_occupancies, game.get_piece_bitboard(Piece::BlackKing), game.get_piece_bitboard(Piece::WhiteKing))
+    };
+    if own_king.is_empty() || enemy_king.is_empty() {
+        return;
+    }
+    let own_king = own_king.least_significant();
+    let enemy_king = enemy_king.least_significant();
+
+    let endgame = non_pawn_material(game, Color::White) + non_pawn_material(game, Color::Black) <= ENDGAME_MATERIAL;
+    let pawn_ending = non_pawn_material(game, opposite_color(color)) == 0;
+
+    while !passed.is_empty() {
+        let square = passed.extract_bit();
+        let (relative_rank, stop, promotion) = if color == Color::White {
+            (LOOKUP_RANK[square as usize], square - 8, square % 8)
+        } else {
+            (7 - LOOKUP_RANK[square as usize], square + 8, 56 + square % 8)
+        };
+
+        //Blockaded passer penalty
+        if enemy_occupancies.get_bit(stop) {
+            acc.add(EvalTerm::PassedPawns, color, square, BLOCKADED_PASSER_PENALTY[relative_rank]);
+        }
+
+        //King proximity to the promotion square
+        if endgame {
+            let distance = 5 * square_distance(enemy_king, promotion) - 2 * square_distance(own_king, promotion);
+            acc.add(EvalTerm::PassedPawns, color, square, distance * KING_PASSER_DISTANCE_WEIGHT[relative_rank]);
+        }
+
+        //Rule of the square: the enemy king can not catch a passer with a free path
+        if pawn_ending && game.all_occupancies.and(Bitboard::from_u64(FILE_MASKS[square as usize] & if color == Color::White {
+                WHITE_PASSED_PAWN_MASKS[square as usize]
+            } else {
+                BLACK_PASSED_PAWN_MASKS[square as usize]
+            })).is_empty() {
+            let pawn_distance = 7 - relative_rank as i32 - (relative_rank == 1) as i32;
+            let king_distance = square_distance(enemy_king, promotion) - (game.active_player != color) as i32;
+            if pawn_distance < king_distance {
+                acc.add(EvalTerm::PassedPawns, color, square, UNSTOPPABLE_PASSER_BONUS);
+            }
+        }
+    }
+}
+
+///Attacks collected while evaluating the pieces, [0] = white, [1] = black
+struct AttackInfo {
+    ///The squares around each king
+    zones: [u64; 2],
+    ///Squares attacked by each piece type
+    attacked_by: [[u64; 6]; 2],
+    ///Pieces attacking the enemy king zone, and their summed danger units
+    attackers: [i32; 2],
+    attack_units: [i32; 2],
+}
+
+impl AttackInfo {
+    fn new(game: &Game) -> Self {
+        let mut info = Self { zones: [0; 2], attacked_by: [[0; 6]; 2], attackers: [0; 2], attack_units: [0; 2] };
+
+        info.attacked_by[0][0] = pawn_attacks(game.get_piece_bitboard(Piece::WhitePawn).to_u64(), Color::White);
+        info.attacked_by[1][0] = pawn_attacks(game.get_piece_bitboard(Piece::BlackPawn).to_u64(), Color::Black);
+
+        for (side, king) in [(0, Piece::WhiteKing), (1, Piece::BlackKing)] {
+            let king = game.get_piece_bitboard(king);
+            if king.is_empty() {
+                continue;
+            }
+            let square = king.least_significant();
+            let around = get_king_attack_table(square).to_u64();
+            info.attacked_by[side][5] = around;
+
+            //The zone reaches one rank further towards the enemy
+            let zone = around | 1 << square;
+            info.zones[side] = zone | if side == 0 { zone >> 8 } else { zone << 8 };
+        }
+
+        info
+    }
+
+    #[inline(always)]
+    fn add(&mut self, side: usize, piece_type: usize, attacks: u64) {
+        self.attacked_by[side][piece_type] |= attacks;
+        if attacks & self.zones[1 - side] != 0 {
+            self.attackers[side] += 1;
+            self.attack_units[side] += KING_ATTACK_WEIGHTS[piece_type] * (attacks & self.zones[1 - side]).count_ones() as i32;
+        }
+    }
+
+    fn all_attacks(&self, side: usize) -> u64 {
+        self.attacked_by[side].iter().fold(0, |all, a| all | a)
+    }
+}
+
+///Pawn shield, pawn storms, and the danger from enemy pieces attacking the king zone or able to give safe checks
+#[inline(always)]
+fn evaluate_king_safety<T: EvalTracer>(game: &Game, color: Color, info: &AttackInfo, acc: &mut EvalAccumulator<T>) {
+    let (side, enemy, offset, enemy_offset) = if color == Color::White { (0, 1, 0, 6) } else { (1, 0, 6, 0) };
+
+    let king = game.bitboards[offset + 5];
+    if king.is_empty() {
+        return;
+    }
+    let square = king.least_significant();
+    let relative_rank = if color == Color::White { LOOKUP_RANK[square as usize] } else { 7 - LOOKUP_RANK[square as usize] };
+
+    let own_pawns = game.bitboards[offset].to_u64();
+    let enemy_pawns = game.bitboards[enemy_offset].to_u64();
+    let files = FILE_MASKS[square as usize] | ISOLATED_MASKS[square as usize];
+
+    let mut units = 0;
+
+    //Pawn shield, only for a king that stays home
+    if relative_rank <= 1 {
+        let shield = (own_pawns & files & ranks_ahead(square, color, 2)).count_ones() as i32;
+        acc.add(EvalTerm::KingSafety, color, square, shield * PAWN_SHIELD_BONUS);
+        units += 3 - shield.min(3);
+    }
+
+    //Pawn storm
+    let storm = (enemy_pawns & files & ranks_ahead(square, color, 3)).count_ones() as i32;
+    acc.add(EvalTerm::KingSafety, color, square, storm * PAWN_STORM_PENALTY);
+
+    //Attacks are only dangerous with the enemy queen on the board
+    if game.bitboards[enemy_offset + 4].is_empty() || info.attackers[enemy] < 2 {
+        return;
+    }
+    units += info.attack_units[enemy];
+
+    //Safe checks land on squares we do not defend and that are not blocked by enemy pieces
+    let enemy_occupancies = if color == Color::White { game.black_occupancies } else { game.white_occupancies };
+    let safe = !info.all_attacks(side) & !enemy_occupancies.to_u64();
+    let bishop_checks = get_bishop_attack_table(square, game.all_occupancies).to_u64() & safe;
+    let rook_checks = get_rook_attack_table(square, game.all_occupancies).to_u64() & safe;
+    let knight_checks = get_knight_attack_table(square).to_u64() & safe;
+
+    for (piece_type, checks) in [(1, knight_checks), (2, bishop_checks), (3, rook_checks), (4, bishop_checks | rook_checks)] {
+        if checks & info.attacked_by[enemy][piece_type] != 0 {
+            units += SAFE_CHECK_WEIGHTS[piece_type];
+        }
+    }
+
+    acc.add(EvalTerm::KingSafety, color, square, -KING_DANGER[units.clamp(0, 99) as usize]);
+}
+
+///The given number of ranks in front of the square, from the perspective of color
+#[inline(always)]
+fn ranks_ahead(square: u8, color: Color, count: i32) -> u64 {
+    let row = (square / 8) as i32;
+    let mut mask = 0;
+    for i in 1..=count {
+        let ahead = if color == Color::White { row - i } else { row + i };
+        if (0..8).contains(&ahead) {
+            mask |= RANK_MASKS[ahead as usize * 8];
+        }
+    }
+    mask
+}
+
+///Phase of the game from the remaining pieces, MAX_PHASE in the opening and 0 in pawn endings
+#[inline(always)]
+fn game_phase(game: &Game) -> i32 {
+    let phase: i32 = (0..12).map(|p| game.bitboards[p].to_u64().count_ones() as i32 * PHASE_WEIGHTS[p % 6]).sum();
+    phase.min(MAX_PHASE)
+}
+
+///Blends a (middlegame, endgame) pair by the game phase
+#[inline(always)]
+fn taper((middlegame, endgame): (i32, i32), phase: i32) -> i32 {
+    (middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+///Material of the knights, bishops, rooks and queens of a side
+#[inline(always)]
+pub fn non_pawn_material(game: &Game, color: Color) -> i32 {
+    let offset = if color == Color::White { 0 } else { 6 };
+    (1..5).map(|p| game.bitboards[offset + p].to_u64().count_ones() as i32 * MATERIAL_WEIGHTS[p]).sum()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EvalTerm {
+    Material,
+    PieceSquare,
+    StackedPawns,
+    IsolatedPawns,
+    BackwardPawns,
+    ConnectedPawns,
+    PassedPawns,
+    CandidatePawns,
+    RookFiles,
+    Mobility,
+    KingSafety,
+    Variant,
+    Endgame,
+}
+
+pub const EVAL_TERM_COUNT: usize = 13;
+pub const EVAL_TERM_STRINGS: [&str; EVAL_TERM_COUNT] = ["Material", "Piece-square", "Stacked pawns", "Isolated pawns", "Backward pawns", "Connected pawns",
+                                                        "Passed pawns", "Candidate pawns", "Rook files", "Mobility", "King safety", "Variant", "Endgame"];
+
+///Receives every evaluation term as it is added. Values are from the perspective of `color`
+pub trait EvalTracer {
+    fn add(&mut self, term: EvalTerm, color: Color, square: u8, value: i32);
+}
+
+impl EvalTracer for () {
+    #[inline(always)]
+    fn add(&mut self, _term: EvalTerm, _color: Color, _square: u8, _value: i32) {}
+}
+
+struct EvalAccumulator<'a, T: EvalTracer> {
+    score: i32,
+    tracer: &'a mut T,
+}
+
+impl<'a, T: EvalTracer> EvalAccumulator<'a, T> {
+    #[inline(always)]
+    fn add(&mut self, term: EvalTerm, color: Color, square: u8, value: i32) {
+        self.score += if color == Color::White { value } else { -value };
+        self.tracer.add(term, color, square, value);
+    }
+}
+
+///Breakdown of an evaluation, collected through `evaluate_traced`
+pub struct EvalTrace {
+    ///[term][0 = white, 1 = black], from each side's own perspective
+    pub terms: [[i32; 2]; EVAL_TERM_COUNT],
+    ///Positional contribution (everything but material and endgame knowledge) of the piece on each square, from white's perspective
+    pub squares: [i32; 64],
+}
+
+impl EvalTracer for EvalTrace {
+    fn add(&mut self, term: EvalTerm, color: Color, square: u8, value: i32) {
+        let side = if color == Color::White { 0 } else { 1 };
+        self.terms[term as usize][side] += value;
+        if term != EvalTerm::Material && term != EvalTerm::Endgame {
+            self.squares[square as usize] += if color == Color::White { value } else { -value };
+        }
+    }
+}
+
+impl EvalTrace {
+    pub fn new() -> Self {
+        Self { terms: [[0; 2]; EVAL_TERM_COUNT], squares: [0; 64] }
+    }
+
+    pub fn white_total(&self) -> i32 {
+        self.terms.iter().map(|t| t[0]).sum()
+    }
+
+    pub fn black_total(&self) -> i32 {
+        self.terms.iter().map(|t| t[1]).sum()
+    }
+
+    pub fn print(&self, game: &Game) {
+        println!("\n  Term              │    White │    Black │    Total");
+        println!("  ──────────────────┼──────────┼──────────┼─────────");
+        for (name, [white, black]) in EVAL_TERM_STRINGS.iter().zip(self.terms) {
+            println!("  {:<17} │ {:>8} │ {:>8} │ {:>8}", name, white, black, white - black);
+        }
+        println!("  ──────────────────┼──────────┼──────────┼─────────");
+        println!("  {:<17} │ {:>8} │ {:>8} │ {:>8}", "Total", self.white_total(), self.black_total(), self.white_total() - self.black_total());
+
+        let total = self.white_total() - self.black_total();
+        let active = if game.active_player == Color::White { "White" } else { "Black" };
+        println!("\n  Side to move ({}): {}", active, if game.active_player == Color::White { total } else { -total });
+
+        //Heat map of positional piece contributions, from white's perspective
+        println!("\n  ┌──────┬──────┬──────┬──────┬──────┬──────┬──────┬──────┐");
+        for y in 0..8 {
+            print!("{} │", 8 - y);
+            for x in 0..8 {
+                let square = 8 * y + x;
+                if game.all_occupancies.get_bit(square) {
+                    print!("{:>5} ", self.squares[square as usize]);
+                } else {
+                    print!("      ");
+                }
+                if x != 7 { print!("│") };
+            }
+            println!("│");
+            if y != 7 { println!("  ├──────┼──────┼──────┼──────┼──────┼──────┼──────┼──────┤") };
+        }
+        println!("  └──────┴──────┴──────┴──────┴──────┴──────┴──────┴──────┘");
+        println!("      a      b      c      d      e      f      g      h\n");
+    }
+}
+
+///All squares attacked by the given pawns
+#[inline(always)]
+pub fn pawn_attacks(pawns: u64, color: Color) -> u64 {
+    const NOT_A_FILE: u64 = 0xfefefefefefefefe;
+    const NOT_H_FILE: u64 = 0x7f7f7f7f7f7f7f7f;
+    if color == Color::White {
+        ((pawns & NOT_A_FILE) >> 9) | ((pawns & NOT_H_FILE) >> 7)
+    } else {
+        ((pawns & NOT_A_FILE) << 7) | ((pawns & NOT_H_FILE) << 9)
+    }
 }
 
 const fn generate_file_masks() -> [u64; 64] {
@@ -365,7 +965,7 @@ const fn generate_rank_masks() -> [u64; 64] {
             
             let mut i = 0;
             while i < 8 {
-                mask |= (1 << i) << 8*f;
+                mask |= (1 << i) << 8*r;
                 i += 1;
             }
 
@@ -422,10 +1022,11 @@ const fn generate_white_passed_pawn_masks() -> [u64; 64] {
             if f < 7 {
                 mask |= FILE_MASKS[r*8+f + 1]
             }
-            //For all ranks lower
+            //For the own rank and all ranks lower
             let mut rr = 7;
-            while rr > r {
+            while rr >= r {
                 mask ^= RANK_MASKS[rr*8] & mask;
+                if rr == 0 { break; }
                 rr -= 1;
             }
             masks[r*8+f] = mask;
@@ -455,9 +1056,9 @@ const fn generate_black_passed_pawn_masks() -> [u64; 64] {
             if f < 7 {
                 mask |= FILE_MASKS[r*8+f + 1]
             }
-            //For all ranks lower
+            //For the own rank and all ranks higher
             let mut rr = 0;
-            while rr < r {
+            while rr <= r {
                 mask ^= RANK_MASKS[rr*8] & mask;
                 rr += 1;
             }
@@ -473,7 +1074,7 @@ const fn generate_black_passed_pawn_masks() -> [u64; 64] {
 
 #[cfg(test)]
 mod eval_tests {
-    use crate::*;
+    use super::*;
 
     #[test]
     pub fn eval () {
@@ -481,4 +1082,110 @@ mod eval_tests {
         game.pretty_print();
         println!("{}", evaluate(&game));
     }
+
+    #[test]
+    pub fn trace_adds_up_to_evaluation() {
+        let game = Game::new_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1").unwrap();
+        let mut trace = EvalTrace::new();
+        let score = evaluate_traced(&game, &mut trace);
+
+        assert_eq!(score, evaluate(&game));
+        assert_eq!(score, -(trace.white_total() - trace.black_total()));
+        let material = trace.terms[EvalTerm::Material as usize];
+        assert_eq!(trace.squares.iter().sum::<i32>(), trace.white_total() - trace.black_total() - (material[0] - material[1]));
+    }
+
+    fn trace_of(fen: &str) -> EvalTrace {
+        let mut trace = EvalTrace::new();
+        evaluate_traced(&Game::new_from_fen(fen).unwrap(), &mut trace);
+        trace
+    }
+
+    #[test]
+    pub fn passed_pawns_ignore_pawns_behind() {
+        //The black pawn on d3 is behind the e4 pawn, which is passed
+        let trace = trace_of("4k3/8/8/8/4P3/3p4/8/4K3 w - - 0 1");
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][0] > 0);
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][1] > 0);
+
+        //d5 stops e4
+        let trace = trace_of("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::PassedPawns as usize][0], 0);
+    }
+
+    #[test]
+    pub fn rank_masks_cover_the_rank_of_the_square() {
+        //Indexed by rank, not by file
+        assert_eq!(RANK_MASKS[Square::d4 as usize], 0xff << 32);
+        assert_eq!(RANK_MASKS[Square::h8 as usize], 0xff);
+        assert_eq!(RANK_MASKS[Square::a1 as usize], 0xff << 56);
+    }
+
+    #[test]
+    pub fn passed_pawns_ignore_pawns_beside() {
+        //A pawn on an adjacent file and the same rank can't stop the passer anymore
+        assert_eq!(WHITE_PASSED_PAWN_MASKS[Square::e4 as usize] & RANK_MASKS[Square::e4 as usize], 0);
+        assert_eq!(BLACK_PASSED_PAWN_MASKS[Square::e5 as usize] & RANK_MASKS[Square::e5 as usize], 0);
+
+        let trace = trace_of("4k3/8/8/8/3pP3/8/8/4K3 w - - 0 1");
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][0] > 0);
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][1] > 0);
+    }
+
+    #[test]
+    pub fn backward_and_connected_pawns() {
+        //d2 can not be supported and d3 is guarded by e4
+        let trace = trace_of("4k3/8/8/8/2P1p3/8/3P4/4K3 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::BackwardPawns as usize][0], BACKWARD_PAWN_PENALTY);
+
+        let trace = trace_of("4k3/8/8/8/2PP4/8/8/4K3 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::ConnectedPawns as usize][0], 2 * CONNECTED_PAWN_BONUS[3]);
+    }
+
+    #[test]
+    pub fn candidate_passer() {
+        //c4 and d4 against d5: c4 has a helper for its single sentry
+        let trace = trace_of("4k3/8/8/3p4/2PP4/8/8/4K3 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::CandidatePawns as usize][0], CANDIDATE_PASSER_BONUS[3]);
+    }
+
+    #[test]
+    pub fn mobility_counts_safe_squares() {
+        //f5 is guarded by the e6 pawn, the rest of the knight's squares are safe
+        let trace = trace_of("4k3/8/4p3/8/3N4/8/8/4K3 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::Mobility as usize][0], taper(KNIGHT_MOBILITY[7], 1));
+
+        //Own pieces block mobility
+        let trace = trace_of("4k3/8/8/8/8/8/1P6/B3K3 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::Mobility as usize][0], taper(BISHOP_MOBILITY[0], 1));
+
+        assert_eq!(game_phase(&Game::new_from_start_pos()), MAX_PHASE);
+    }
+
+    #[test]
+    pub fn king_shield_and_attackers() {
+        let trace = trace_of("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(trace.terms[EvalTerm::KingSafety as usize][0], 3 * PAWN_SHIELD_BONUS);
+        assert_eq!(trace.terms[EvalTerm::KingSafety as usize][0], trace.terms[EvalTerm::KingSafety as usize][1]);
+
+        //Queen and knight bearing down on h2
+        let attacked = trace_of("qn4k1/5ppp/8/8/6nq/8/5PPP/6K1 w - - 0 1");
+        let quiet = trace_of("qn4k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert!(attacked.terms[EvalTerm::KingSafety as usize][0] < quiet.terms[EvalTerm::KingSafety as usize][0]);
+    }
+
+    #[test]
+    pub fn rule_of_the_square() {
+        //The black king on a4 is outside the square of g5
+        let trace = trace_of("8/8/8/6P1/k7/8/8/K7 b - - 0 1");
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][0] >= UNSTOPPABLE_PASSER_BONUS);
+
+        //From d5 it catches the pawn
+        let trace = trace_of("8/8/8/3k2P1/8/8/8/K7 b - - 0 1");
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][0] < UNSTOPPABLE_PASSER_BONUS);
+
+        //With a rook the king does not need to catch it
+        let trace = trace_of("8/8/8/6P1/k7/8/8/K6r b - - 0 1");
+        assert!(trace.terms[EvalTerm::PassedPawns as usize][0] < UNSTOPPABLE_PASSER_BONUS);
+    }
 }
\ No newline at end of file
@@ -1,5 +1,12 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use super::*;
 
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+///Write castling as the king taking its own rook, set by the UCI_Chess960 option
+pub static UCI_CHESS960: AtomicBool = AtomicBool::new(false);
+
 #[derive(Clone, Copy)]
 pub struct Game {
     pub bitboards: [Bitboard; 12],
@@ -12,10 +19,22 @@ pub struct Game {
     pub active_player: Color,
     pub enpassant_square: Square,
     pub castling_ability: u8,
+    //Starting squares of the castling rooks: white king side, white queen side, black king side, black queen side
+    pub castling_rooks: [u8; 4],
+
+    pub variant: Variant,
+    //Checks given by white and black in Three-check
+    pub checks_given: [u8; 2],
+    //Pieces in hand in Crazyhouse, by color and piece % 6 from pawn to queen, and the pieces that were promoted
+    pub pockets: [[u8; 5]; 2],
+    pub promoted: Bitboard,
 
     pub full_moves: u16,
     pub half_moves: u8,
     pub zobrist_hash: u64,
+    pub pawn_hash: u64,
+
+    pub accumulator: Accumulator,
 }
 
 impl Game {
@@ -54,19 +73,111 @@ impl Game {
         println!("\tHalf moves: {}",    self.half_moves);
         print!("   Castling:   {}  ", self.castling_ability_string());
         println!("\tZobrist:   {:#0x}\n", self.make_zobrist_hash());
+        if self.variant == Variant::Crazyhouse {
+            println!("   Pockets:    [{}]\n", self.pocket_string());
+        }
     }
 
+    ///X-FEN castling field: KQkq for the outermost rooks, and the rook's file as in Shredder-FEN otherwise
     fn castling_ability_string(&self) -> String {
         let mut result = String::new();
-        if self.castling_ability & CastlingAbility::WhiteKingSide as u8 != 0    { result += "K" }
-        if self.castling_ability & CastlingAbility::WhiteQueenSide as u8 != 0   { result += "Q" }
-        if self.castling_ability & CastlingAbility::BlackKingSide as u8 != 0    { result += "k" }
-        if self.castling_ability & CastlingAbility::BlackQueenSide as u8 != 0   { result += "q" }
+        for (right, letter) in ['K', 'Q', 'k', 'q'].into_iter().enumerate() {
+            if self.castling_ability & 1 << right == 0 {
+                continue;
+            }
+
+            let rook = self.castling_rooks[right];
+            let rooks = self.bitboards[if right < 2 { Piece::WhiteRook } else { Piece::BlackRook } as usize].to_u64();
+            let outer = if right % 2 == 0 { rook + 1..(rook / 8 + 1) * 8 } else { rook / 8 * 8..rook };
+            if outer.into_iter().all(|sq| rooks & 1 << sq == 0) {
+                result.push(letter);
+            }
+            else {
+                let file = (b'a' + rook % 8) as char;
+                result.push(if right < 2 { file.to_ascii_uppercase() } else { file });
+            }
+        }
+        result
+    }
+
+    ///Crazyhouse pockets like QNPqp, white first
+    fn pocket_string(&self) -> String {
+        let mut result = String::new();
+        for side in 0..2 {
+            for piece_type in (0..5).rev() {
+                for _ in 0..self.pockets[side][piece_type] {
+                    result += PIECE_STRINGS[side * 6 + piece_type];
+                }
+            }
+        }
+        result
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut result = String::new();
+        for rank in 0..8 {
+            let mut empty = 0;
+            for file in 0..8 {
+                let square = rank * 8 + file;
+                match (0..12).find(|p| self.bitboards[*p].get_bit(square)) {
+                    Some(piece) => {
+                        if empty != 0 { result += &empty.to_string(); empty = 0; }
+                        result += PIECE_STRINGS[piece];
+                        if self.variant == Variant::Crazyhouse && self.promoted.get_bit(square) { result.push('~'); }
+                    },
+                    None => empty += 1
+                }
+            }
+            if empty != 0 { result += &empty.to_string(); }
+            if rank != 7 { result += "/"; }
+        }
+        if self.variant == Variant::Crazyhouse {
+            result += &format!("[{}]", self.pocket_string());
+        }
+
+        result += if self.active_player == Color::White { " w " } else { " b " };
+        let castling = self.castling_ability_string();
+        result += if castling.is_empty() { "-" } else { castling.as_str() };
+        result += " ";
+        result += SQUARE_STRINGS[self.enpassant_square as usize].replace("None", "-").as_str();
+        if self.variant == Variant::ThreeCheck {
+            result += &format!(" {}+{}", 3 - self.checks_given[0].min(3), 3 - self.checks_given[1].min(3));
+        }
+        result += &format!(" {} {}", self.half_moves, self.full_moves);
+
         result
     }
 
     pub fn new_from_start_pos() -> Self {
-        Game::new_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()
+        Game::new_from_fen(START_FEN).unwrap()
+    }
+
+    ///Chess960 starting position 0 to 959 in Scharnagl's numbering, where 518 is the standard one
+    pub fn new_chess960(number: usize) -> Self {
+        //Puts the piece on the index-th empty file
+        fn place(back_rank: &mut [char; 8], index: usize, piece: char) {
+            let file = (0..8).filter(|f| back_rank[*f] == ' ').nth(index).unwrap();
+            back_rank[file] = piece;
+        }
+
+        let mut back_rank = [' '; 8];
+        let mut n = number % 960;
+        back_rank[n % 4 * 2 + 1] = 'b';
+        n /= 4;
+        back_rank[n % 4 * 2] = 'b';
+        n /= 4;
+        place(&mut back_rank, n % 6, 'q');
+        n /= 6;
+
+        let (first, second) = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)][n];
+        place(&mut back_rank, second, 'n');
+        place(&mut back_rank, first, 'n');
+        for piece in ['r', 'k', 'r'] {
+            place(&mut back_rank, 0, piece);
+        }
+
+        let pieces: String = back_rank.iter().collect();
+        Game::new_from_fen(&format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", pieces, pieces.to_uppercase())).unwrap()
     }
 
     pub fn new_from_fen(input: &str) -> Option<Self> {
@@ -83,8 +194,30 @@ impl Game {
         if split.peek().is_none() { return None }
         let board_str = split.next().unwrap();
 
+        //Crazyhouse pockets follow the board in brackets, or as a ninth rank
+        let (board_str, pocket_str) = match board_str.split_once('[') {
+            Some((board, pocket)) => (board, pocket.trim_end_matches(']')),
+            None => match board_str.match_indices('/').nth(7) {
+                Some((index, _)) => (&board_str[..index], &board_str[index + 1..]),
+                None => (board_str, "")
+            }
+        };
+
+        let mut pockets = [[0; 5]; 2];
+        for char in pocket_str.chars().filter(|c| *c != '-') {
+            let piece = char_to_piece(char)? as usize;
+            if piece % 6 == 5 { return None };
+            pockets[piece / 6][piece % 6] += 1;
+        }
+
+        //Promoted pieces are marked with a ~ after them
+        let mut promoted = Bitboard::new();
+
         for char in board_str.chars() {
-            if char.is_numeric(){
+            if char == '~' {
+                if i > 0 { promoted.set_bit(i - 1) };
+            }
+            else if char.is_numeric(){
                 for _i in 0..char.to_digit(10).unwrap_or(0) {
                     i += 1;
                 }
@@ -104,16 +237,43 @@ impl Game {
         let active = split.next().unwrap();
         let active_color = if active == "w" { Color::White } else { Color::Black };
 
+        //KQkq for the outermost rooks as in X-FEN, or the files of the rooks as in Shredder-FEN
         let castling_str =  if split.peek().is_some() { split.next().unwrap() } else { "" };
         let mut castling_ability: u8 = 0;
-        if castling_str.contains('K') {castling_ability = castling_ability | CastlingAbility::WhiteKingSide as u8 }
-        if castling_str.contains('Q') {castling_ability = castling_ability | CastlingAbility::WhiteQueenSide as u8}
-        if castling_str.contains('k') {castling_ability = castling_ability | CastlingAbility::BlackKingSide as u8}
-        if castling_str.contains('q') {castling_ability = castling_ability | CastlingAbility::BlackQueenSide as u8}
+        let mut castling_rooks = [Square::h1 as u8, Square::a1 as u8, Square::h8 as u8, Square::a8 as u8];
+        for c in castling_str.chars() {
+            let (color, back_rank) = if c.is_ascii_uppercase() { (0, 56) } else { (1, 0) };
+            let king = bitboards[color * 6 + 5].to_u64() & 0xff << back_rank;
+            if king == 0 {
+                continue;
+            }
+
+            let king_file = king.trailing_zeros() as u8 % 8;
+            let rooks = (bitboards[color * 6 + 3].to_u64() >> back_rank) & 0xff;
+            let file = match c.to_ascii_lowercase() {
+                'k' => (king_file + 1..8).rev().find(|f| rooks & 1 << f != 0),
+                'q' => (0..king_file).find(|f| rooks & 1 << f != 0),
+                f @ 'a'..='h' => Some(f as u8 - b'a').filter(|f| rooks & 1 << f != 0 && *f != king_file),
+                _ => None
+            };
+
+            if let Some(file) = file {
+                let right = color * 2 + if file > king_file { 0 } else { 1 };
+                castling_ability |= 1 << right;
+                castling_rooks[right] = back_rank + file;
+            }
+        }
 
         let enpassant = if split.peek().is_some() { split.next().unwrap() } else { "-" };
         let enpassant_sq: Square = if enpassant != "-" { square_from_string(enpassant) } else { Square::None };
 
+        //Checks left for white and black in Three-check, like 3+3
+        let mut checks_given = [0; 2];
+        if let Some((white, black)) = split.peek().and_then(|c| c.split_once('+')) {
+            checks_given = [3 - white.parse::<u8>().ok()?.min(3), 3 - black.parse::<u8>().ok()?.min(3)];
+            split.next();
+        }
+
         let half_moves: u8 =  if split.peek().is_some() { split.next().unwrap().parse::<u8>().unwrap()  } else { 0 };
         let full_moves: u16 = if split.peek().is_some() { split.next().unwrap().parse::<u16>().unwrap() } else { 0 };
 
@@ -125,18 +285,36 @@ impl Game {
 
             active_player: active_color,
             castling_ability: castling_ability,
+            castling_rooks,
             enpassant_square: enpassant_sq,
 
+            variant: Variant::Standard,
+            checks_given,
+            pockets,
+            promoted,
+
             full_moves: full_moves,
             half_moves: half_moves,
-            zobrist_hash: 0
+            zobrist_hash: 0,
+            pawn_hash: 0,
+
+            accumulator: Accumulator::new()
         };
 
         game.zobrist_hash = game.make_zobrist_hash();
+        game.pawn_hash = game.make_pawn_hash();
 
         Some(game)
     }
 
+    ///Position played under the rules of the variant
+    pub fn new_variant_from_fen(input: &str, variant: Variant) -> Option<Self> {
+        let mut game = Game::new_from_fen(input)?;
+        game.variant = variant;
+        game.zobrist_hash = game.make_zobrist_hash();
+        Some(game)
+    }
+
     #[inline(always)]
     pub fn is_square_attacked(&self, square: u8, by_color: Color) -> bool {
         return if by_color == Color::White {
@@ -157,6 +335,22 @@ impl Game {
         }
     }
 
+    ///All pieces of both colors attacking the square, given the occupancy
+    #[inline(always)]
+    pub fn attackers_to(&self, square: u8, occupancy: Bitboard) -> Bitboard {
+        let bishops = self.bitboards[Piece::WhiteBishop as usize].or(self.bitboards[Piece::BlackBishop as usize])
+                        .or(self.bitboards[Piece::WhiteQueen as usize]).or(self.bitboards[Piece::BlackQueen as usize]);
+        let rooks = self.bitboards[Piece::WhiteRook as usize].or(self.bitboards[Piece::BlackRook as usize])
+                        .or(self.bitboards[Piece::WhiteQueen as usize]).or(self.bitboards[Piece::BlackQueen as usize]);
+
+        get_pawn_attack_table(square, Color::Black).and(self.bitboards[Piece::WhitePawn as usize])
+            .or(get_pawn_attack_table(square, Color::White).and(self.bitboards[Piece::BlackPawn as usize]))
+            .or(get_knight_attack_table(square).and(self.bitboards[Piece::WhiteKnight as usize].or(self.bitboards[Piece::BlackKnight as usize])))
+            .or(get_king_attack_table(square).and(self.bitboards[Piece::WhiteKing as usize].or(self.bitboards[Piece::BlackKing as usize])))
+            .or(get_bishop_attack_table(square, occupancy).and(bishops))
+            .or(get_rook_attack_table(square, occupancy).and(rooks))
+    }
+
     #[cfg(test)]
     #[allow(dead_code)]
     pub fn print_attacked_squares (&self, by_color: Color) {
@@ -169,6 +363,19 @@ impl Game {
 
     #[inline(always)]
     pub fn is_in_check(&self, color: Color) -> bool {
+        //The Horde has no king to check
+        if self.variant == Variant::Horde && self.bitboards[if color == Color::White { Piece::WhiteKing } else { Piece::BlackKing } as usize].is_empty() {
+            return false;
+        }
+
+        //In Atomic a king can't be checked once a king exploded, nor by a king next to it
+        if self.variant == Variant::Atomic {
+            let kings = [self.bitboards[Piece::WhiteKing as usize], self.bitboards[Piece::BlackKing as usize]];
+            if kings.iter().any(|k| k.is_empty()) || !get_king_attack_table(kings[0].least_significant()).and(kings[1]).is_empty() {
+                return false;
+            }
+        }
+
         if color == Color::White {
             self.is_square_attacked(self.get_piece_bitboard(Piece::WhiteKing).least_significant(), Color::Black)
         }
@@ -182,15 +389,186 @@ impl Game {
         self.bitboards[piece as usize]
     }
 
+    ///Finds the legal move matching a move in UCI. Castling is also found in the notation not currently used
     pub fn parse_move(&mut self, input: String) -> Option<Move> {
         let moves = generate_moves(self, MoveTypes::All).legal_values(self);
-        let m = moves.iter().find(|m| m.to_uci() == input);
-        match m {
-            None => None,
-            Some(mm) => Some(*mm)
+        let chess960 = UCI_CHESS960.load(Ordering::Relaxed);
+        moves.iter().find(|m| self.uci_string(m, chess960) == input)
+            .or_else(|| moves.iter().find(|m| m.is_castling() && self.uci_string(m, !chess960) == input))
+            .copied()
+    }
+
+    ///The move in UCI, with castling as the king taking its own rook with the UCI_Chess960 option
+    pub fn move_to_uci(&self, cmove: &Move) -> String {
+        self.uci_string(cmove, UCI_CHESS960.load(Ordering::Relaxed))
+    }
+
+    fn uci_string(&self, cmove: &Move, chess960: bool) -> String {
+        if chess960 && cmove.is_castling() {
+            format!("{}{}", SQUARE_STRINGS[cmove.from_square() as usize], SQUARE_STRINGS[self.castling_rook(cmove) as usize])
+        }
+        else {
+            cmove.to_uci()
         }
     }
 
+    ///Starting square of the rook that a castling move takes along
+    #[inline(always)]
+    pub fn castling_rook(&self, cmove: &Move) -> u8 {
+        let color = if cmove.piece() < 6 { 0 } else { 1 };
+        self.castling_rooks[color * 2 + if cmove.to_square() % 8 == 6 { 0 } else { 1 }]
+    }
+
+    ///Castling rights kept when a piece moves from or to the square, which ends the right of a rook starting there
+    #[inline(always)]
+    pub fn castling_rights_kept(&self, square: u8) -> u8 {
+        let mut rights = 15;
+        for right in 0..4 {
+            if self.castling_rooks[right] == square {
+                rights &= !(1 << right);
+            }
+        }
+        rights
+    }
+
+    ///Finds the legal move matching a move in SAN, like "Nbd7", "exd5", "e8=Q+" or "O-O".
+    ///Lenient about check and annotation suffixes, "0-0", missing or extra "x" and "=", "e.p." and long forms like "Ng1-f3"
+    pub fn parse_san(&mut self, input: &str) -> Option<Move> {
+        let san: String = input.trim().trim_end_matches("e.p.").trim_end()
+            .chars().filter(|c| !"+#!?-x:=".contains(*c)).collect();
+        let moves = generate_moves(self, MoveTypes::All).legal_values(self);
+
+        if san == "OO" || san == "00" || san == "OOO" || san == "000" {
+            let file = if san.len() == 2 { 6 } else { 2 };
+            return moves.into_iter().find(|m| m.is_castling() && m.to_square() % 8 == file);
+        }
+
+        //Piece letters, also in lowercase except for the b file
+        let (piece_type, rest) = match san.chars().next()? {
+            c @ ('P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'n' | 'r' | 'q' | 'k') => ("PNBRQK".find(c.to_ascii_uppercase())? as u8, &san[1..]),
+            _ => (0, san.as_str())
+        };
+
+        let (rest, promotion) = match rest.char_indices().last()? {
+            (i, c @ ('N' | 'B' | 'R' | 'Q' | 'n' | 'b' | 'r' | 'q')) if piece_type == 0 => (&rest[..i], Some("PNBRQK".find(c.to_ascii_uppercase())? as u8)),
+            _ => (rest, None)
+        };
+
+        if rest.len() < 2 || !rest.is_ascii() {
+            return None;
+        }
+        let (disambiguation, to) = rest.split_at(rest.len() - 2);
+        let to_bytes = to.as_bytes();
+        if !(b'a'..=b'h').contains(&to_bytes[0]) || !(b'1'..=b'8').contains(&to_bytes[1]) {
+            return None;
+        }
+        let to = square_from_string(to) as u8;
+
+        let mut candidates = moves.into_iter().filter(|m| {
+            let from = m.from_square();
+            m.piece() % 6 == piece_type
+                && !m.is_castling()
+                && m.is_drop() == disambiguation.starts_with('@')
+                && m.to_square() == to
+                && (if m.promotion() == Piece::None as u8 { None } else { Some(m.promotion() % 6) }) == promotion
+                && disambiguation.chars().all(|c| match c {
+                    'a'..='h' => from % 8 == c as u8 - b'a',
+                    '1'..='8' => 8 - from / 8 == c as u8 - b'0',
+                    '@' => true,
+                    _ => false
+                })
+        });
+
+        let found = candidates.next()?;
+        if candidates.next().is_some() { None } else { Some(found) }
+    }
+
+    ///The move in SAN, with the least disambiguation needed and a check or mate suffix
+    pub fn move_to_san(&mut self, cmove: &Move) -> String {
+        let from = cmove.from_square();
+        let to = cmove.to_square();
+        let piece_type = cmove.piece() % 6;
+
+        let mut san = if cmove.is_castling() {
+            if to % 8 == 6 { "O-O".to_string() } else { "O-O-O".to_string() }
+        }
+        else if cmove.is_drop() {
+            cmove.to_uci()
+        }
+        else if piece_type == Piece::WhitePawn as u8 {
+            let mut san = String::new();
+            if cmove.is_capture() {
+                san.push((b'a' + from % 8) as char);
+                san.push('x');
+            }
+            san += SQUARE_STRINGS[to as usize];
+            if cmove.promotion() != Piece::None as u8 {
+                san.push('=');
+                san += PIECE_STRINGS[(cmove.promotion() % 6) as usize];
+            }
+            san
+        }
+        else {
+            let others: Vec<u8> = generate_moves(self, MoveTypes::All).legal_values(self).iter()
+                .filter(|m| m.piece() == cmove.piece() && m.to_square() == to && m.from_square() != from && !m.is_drop())
+                .map(|m| m.from_square())
+                .collect();
+
+            let mut san = PIECE_STRINGS[piece_type as usize].to_string();
+            if !others.is_empty() {
+                let (file, rank) = SQUARE_STRINGS[from as usize].split_at(1);
+                if others.iter().all(|o| o % 8 != from % 8) {
+                    san += file;
+                }
+                else if others.iter().all(|o| o / 8 != from / 8) {
+                    san += rank;
+                }
+                else {
+                    san += SQUARE_STRINGS[from as usize];
+                }
+            }
+            if cmove.is_capture() {
+                san.push('x');
+            }
+            san += SQUARE_STRINGS[to as usize];
+            san
+        };
+
+        let mut copy = *self;
+        make_move(&mut copy, cmove);
+        if copy.is_in_check(copy.active_player) {
+            san.push(if generate_moves(&mut copy, MoveTypes::All).legal_values(&copy).is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    ///The moves in SAN, each played from the position after the previous
+    pub fn line_to_san(&self, line: &[Move]) -> Vec<String> {
+        let mut game = *self;
+        line.iter().map(|m| {
+            let san = game.move_to_san(m);
+            make_move(&mut game, m);
+            san
+        }).collect()
+    }
+
+    ///Zobrist hash of the pawns alone, used as the pawn hash table key
+    pub fn make_pawn_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for piece in [Piece::WhitePawn as usize, Piece::BlackPawn as usize] {
+            let mut bb = self.bitboards[piece];
+            while !bb.is_empty() {
+                let ind = bb.extract_bit();
+
+                hash ^= PIECE_KEYS[piece][ind as usize];
+            }
+        }
+
+        hash
+    }
+
     pub fn make_zobrist_hash(&self) -> u64 {
         let mut hash = 0;
 
@@ -213,13 +591,139 @@ impl Game {
             hash ^= ENPASSANT_KEYS[self.enpassant_square as usize];
         }
 
+        hash ^= CHECK_KEYS[0][self.checks_given[0].min(3) as usize] ^ CHECK_KEYS[1][self.checks_given[1].min(3) as usize];
+
+        for side in 0..2 {
+            for piece_type in 0..5 {
+                hash ^= POCKET_KEYS[side][piece_type][(self.pockets[side][piece_type] as usize).min(16)];
+            }
+        }
+
+        let mut promoted = self.promoted;
+        while !promoted.is_empty() {
+            hash ^= PROMOTED_KEYS[promoted.extract_bit() as usize];
+        }
+
         hash
     }
 }
 
 #[cfg(test)]
 mod make_tests {
-    //use super::*;
+    use super::*;
+
+    #[test]
+    pub fn fen_round_trips() {
+        for fen in ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "rnbqkbnr/p1pppppp/8/Pp6/8/8/1PPPPPPP/RNBQKBNR w KQkq b6 0 2",
+                    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40"] {
+            assert_eq!(Game::new_from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    pub fn parses_chess960_castling() {
+        //Shredder-FEN files, written as X-FEN when the rooks are the outermost ones
+        let game = Game::new_from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+        assert_eq!(game.castling_rooks, [Square::h1 as u8, Square::f1 as u8, Square::h8 as u8, Square::f8 as u8]);
+        assert_eq!(game.to_fen(), "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9");
+
+        //An inner rook needs its file
+        let game = Game::new_from_fen("4k3/8/8/8/8/8/8/R3KR1R w FA - 0 1").unwrap();
+        assert_eq!(game.castling_rooks[0], Square::f1 as u8);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/R3KR1R w FQ - 0 1");
+        assert_eq!(Game::new_from_fen("4k3/8/8/8/8/8/8/R3KR1R w K - 0 1").unwrap().castling_rooks[0], Square::h1 as u8);
+
+        //Rights without a rook are dropped
+        assert_eq!(Game::new_from_fen("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1").unwrap().castling_ability, 0);
+
+        assert_eq!(Game::new_chess960(518).to_fen(), Game::new_from_start_pos().to_fen());
+        assert_eq!(Game::new_chess960(0).to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        let mut fens: Vec<String> = (0..960).map(|n| Game::new_chess960(n).to_fen()).collect();
+        fens.sort();
+        fens.dedup();
+        assert_eq!(fens.len(), 960);
+    }
+
+    #[test]
+    pub fn castles_in_chess960() {
+        //The king and rook swap squares
+        let mut game = Game::new_from_fen("4k3/8/8/8/8/8/8/5KR1 w G - 0 1").unwrap();
+        let castling = game.parse_move("f1g1".to_string()).unwrap();
+        assert!(castling.is_castling());
+        assert_eq!(game.uci_string(&castling, true), "f1g1");
+        assert_eq!(game.move_to_san(&castling), "O-O");
+
+        make_move(&mut game, &castling);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+        assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+        assert_eq!(game.all_occupancies.to_u64().count_ones(), 3);
+
+        //The king stays and the rook jumps over it, the king taking the rook in UCI
+        let mut game = Game::new_from_fen("4k3/8/8/8/8/8/8/1RK4R w B - 0 1").unwrap();
+        let castling = game.parse_move("c1b1".to_string()).unwrap();
+        assert_eq!(castling.to_uci(), "c1c1");
+        assert_eq!(game.uci_string(&castling, true), "c1b1");
+        make_move(&mut game, &castling);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+
+        //Standard castling is also found as the king taking the rook
+        let mut game = Game::new_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(game.parse_move("e1h1".to_string()).unwrap().to_uci(), "e1g1");
+        assert_eq!(game.parse_move("e1a1".to_string()).unwrap().to_uci(), "e1c1");
+    }
+
+    #[test]
+    pub fn parses_san() {
+        let mut game = Game::new_from_fen("r3k2r/1P6/8/3p4/4P3/5N1N/8/R3K2R w KQkq - 0 1").unwrap();
+        let uci = |game: &mut Game, san: &str| game.parse_san(san).map(|m| m.to_uci());
+
+        assert_eq!(uci(&mut game, "exd5"), Some("e4d5".to_string()));
+        assert_eq!(uci(&mut game, "Nhg5"), Some("h3g5".to_string()));
+        assert_eq!(uci(&mut game, "Ng5"), None);
+        assert_eq!(uci(&mut game, "O-O+"), Some("e1g1".to_string()));
+        assert_eq!(uci(&mut game, "O-O-O"), Some("e1c1".to_string()));
+        assert_eq!(uci(&mut game, "bxa8=Q"), Some("b7a8q".to_string()));
+        assert_eq!(uci(&mut game, "b8N"), Some("b7b8n".to_string()));
+        assert_eq!(uci(&mut game, "Kd3"), None);
+
+        //Lenient variants
+        assert_eq!(uci(&mut game, "0-0"), Some("e1g1".to_string()));
+        assert_eq!(uci(&mut game, "ed5"), Some("e4d5".to_string()));
+        assert_eq!(uci(&mut game, "Nh3-g5"), Some("h3g5".to_string()));
+        assert_eq!(uci(&mut game, "ba8q"), Some("b7a8q".to_string()));
+        assert_eq!(uci(&mut game, "Ra1xa8!?"), Some("a1a8".to_string()));
+
+        let mut game = Game::new_from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        assert_eq!(game.parse_san("exd6 e.p.").map(|m| m.to_uci()), Some("e5d6".to_string()));
+    }
+
+    #[test]
+    pub fn formats_san() {
+        let mut game = Game::new_from_fen("r3k2r/1P6/8/3p4/4P3/5N1N/8/R3K2R w KQkq - 0 1").unwrap();
+        let san = |game: &mut Game, uci: &str| {
+            let m = game.parse_move(uci.to_string()).unwrap();
+            game.move_to_san(&m)
+        };
+
+        assert_eq!(san(&mut game, "e4d5"), "exd5");
+        assert_eq!(san(&mut game, "h3g5"), "Nhg5");
+        assert_eq!(san(&mut game, "e1g1"), "O-O");
+        assert_eq!(san(&mut game, "b7a8q"), "bxa8=Q+");
+        assert_eq!(san(&mut game, "a1a8"), "Rxa8+");
+
+        //Knights on the same file need the rank, and queens sharing both need the square
+        let mut game = Game::new_from_fen("4k3/8/8/1N6/8/1N6/8/Q3K2Q w - - 0 1").unwrap();
+        assert_eq!(san(&mut game, "b5d4"), "N5d4");
+        let mut game = Game::new_from_fen("6k1/8/8/8/Q6Q/8/8/Q3K3 w - - 0 1").unwrap();
+        assert_eq!(san(&mut game, "a4d4"), "Qa4d4");
+        assert_eq!(san(&mut game, "h4d4"), "Qhd4");
+
+        let game = Game::new_from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        let line = [Game::new_from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap().parse_move("a1a8".to_string()).unwrap()];
+        assert_eq!(game.line_to_san(&line), vec!["Ra8#"]);
+    }
 
     #[test]
     pub fn zobrist() {
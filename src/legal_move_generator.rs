@@ -0,0 +1,526 @@
+use super::*;
+
+const ALL_SQUARES: u64 = u64::MAX;
+
+///Generates only legal moves: the checkers, pinned pieces and their pin rays are found up front,
+///so that moves never have to be made and taken back to test whether they leave the king in check
+#[inline(always)]
+pub fn generate_legal_moves(game: &Game, move_types: MoveTypes) -> MoveList {
+    if game.variant != Variant::Standard {
+        if is_variant_loss(game) {
+            return MoveList::new();
+        }
+        //Explosions and a missing king don't fit the pin and check masks
+        if game.variant == Variant::Atomic || game.variant == Variant::Horde {
+            return generate_moves_by_copy(game, move_types);
+        }
+    }
+
+    let mut moves = MoveList::new();
+    let captures_only = move_types == MoveTypes::Quiescence;
+    let quiet_checks = move_types == MoveTypes::QuietChecks;
+
+    let (us, them, own, opponent) = if game.active_player == Color::White {
+        (Color::White, Color::Black, 0, 6)
+    }
+    else {
+        (Color::Black, Color::White, 6, 0)
+    };
+    let own_occupancies = if us == Color::White { game.white_occupancies } else { game.black_occupancies }.to_u64();
+    let opponent_occupancies = if us == Color::White { game.black_occupancies } else { game.white_occupancies }.to_u64();
+    let all_occupancies = game.all_occupancies.to_u64();
+
+    //Quiet checks land on a square attacking the enemy king, or uncover a slider by leaving the line to the enemy king
+    let (check_squares, discoverers, enemy_king) = if quiet_checks { check_squares(game, us, own, opponent) } else { ([ALL_SQUARES; 6], 0, 0) };
+    let checks = |from: u8, to: u8, piece: usize| check_squares[piece] & 1 << to != 0
+        || (discoverers & 1 << from != 0 && between(enemy_king, to) & 1 << from == 0 && between(enemy_king, from) & 1 << to == 0);
+
+    let king = game.bitboards[own + 5].least_significant();
+    let checkers = game.attackers_to(king, game.all_occupancies).to_u64() & opponent_occupancies;
+
+    //King moves, with the king taken off the board so it can't hide behind itself from a slider
+    let without_king = Bitboard::from_u64(all_occupancies & !(1 << king));
+    let mut targets = get_king_attack_table(king).to_u64() & !own_occupancies;
+    if captures_only {
+        targets &= opponent_occupancies;
+    }
+    else if quiet_checks {
+        targets &= !all_occupancies;
+    }
+    for to in squares(targets) {
+        if quiet_checks && !checks(king, to, 5) {
+            continue;
+        }
+        if !is_attacked(game, to, without_king, them) {
+            add_move(&mut moves, king, to, own + 5, opponent_occupancies);
+        }
+    }
+
+    //In double check only the king can move
+    if checkers.count_ones() > 1 {
+        return moves;
+    }
+
+    //Other moves must capture the checker or block its ray
+    let check_mask = if checkers != 0 { between(king, checkers.trailing_zeros() as u8) | checkers } else { ALL_SQUARES };
+
+    //Pinned pieces may only move along the ray between the king and the pinner
+    let mut pin_rays = [ALL_SQUARES; 64];
+    let rooks = (game.bitboards[opponent + 3].to_u64() | game.bitboards[opponent + 4].to_u64()) & get_rook_attack_table(king, Bitboard::from_u64(opponent_occupancies)).to_u64();
+    let bishops = (game.bitboards[opponent + 2].to_u64() | game.bitboards[opponent + 4].to_u64()) & get_bishop_attack_table(king, Bitboard::from_u64(opponent_occupancies)).to_u64();
+    for pinner in squares(rooks | bishops) {
+        let ray = between(king, pinner);
+        let blockers = ray & all_occupancies;
+        if blockers.count_ones() == 1 && blockers & own_occupancies != 0 {
+            pin_rays[blockers.trailing_zeros() as usize] = ray | 1 << pinner;
+        }
+    }
+
+    let target_mask = check_mask & if captures_only { opponent_occupancies } else if quiet_checks { !all_occupancies } else { !own_occupancies };
+
+    //Pawns
+    let (forward, start_rank, last_rank): (i8, u8, u8) = if us == Color::White { (-8, 6, 0) } else { (8, 1, 7) };
+    for from in squares(game.bitboards[own].to_u64()) {
+        let allowed = check_mask & pin_rays[from as usize];
+        let promotion = (from as i8 + forward) as u8 / 8 == last_rank;
+
+        if !captures_only {
+            let push = (from as i8 + forward) as u8;
+            if all_occupancies & 1 << push == 0 {
+                if allowed & 1 << push != 0 {
+                    //The promoted piece may check through the square the pawn leaves, rare enough to test in full
+                    if quiet_checks && promotion {
+                        for piece in [4, 1, 3, 2] {
+                            let m = Move::new(from, push, own as u8, (own + piece) as u8, false, false, false, false);
+                            if gives_check(game, &m) {
+                                moves.add_move(m);
+                            }
+                        }
+                    }
+                    else if !quiet_checks || checks(from, push, 0) {
+                        add_pawn_move(&mut moves, from, push, own, promotion, false);
+                    }
+                }
+
+                let double = (push as i8 + forward) as u8;
+                if from / 8 == start_rank && all_occupancies & 1 << double == 0 && allowed & 1 << double != 0 && (!quiet_checks || checks(from, double, 0)) {
+                    moves.add_move(Move::new(from, double, own as u8, Piece::None as u8, false, true, false, false));
+                }
+            }
+        }
+
+        if quiet_checks {
+            continue;
+        }
+
+        let attacks = get_pawn_attack_table(from, us).to_u64();
+        for to in squares(attacks & opponent_occupancies & allowed) {
+            add_pawn_move(&mut moves, from, to, own, promotion, true);
+        }
+
+        if game.enpassant_square != Square::None && attacks & 1 << game.enpassant_square as u8 != 0 {
+            let to = game.enpassant_square as u8;
+            let captured = (to as i8 - forward) as u8;
+
+            //Both pawns leave the rank at once, so test the sliders with the position after the capture
+            let occupancy = Bitboard::from_u64((all_occupancies & !(1 << from) & !(1 << captured)) | 1 << to);
+            if (check_mask & (1 << to | 1 << captured)) != 0 && slider_attackers(game, king, occupancy, opponent) == 0 {
+                moves.add_move(Move::new(from, to, own as u8, Piece::None as u8, true, false, true, false));
+            }
+        }
+    }
+
+    //Pieces
+    for piece in 1..5 {
+        for from in squares(game.bitboards[own + piece].to_u64()) {
+            let attacks = match piece {
+                1 => get_knight_attack_table(from),
+                2 => get_bishop_attack_table(from, game.all_occupancies),
+                3 => get_rook_attack_table(from, game.all_occupancies),
+                _ => get_queen_attack_table(from, game.all_occupancies)
+            }.to_u64();
+
+            for to in squares(attacks & target_mask & pin_rays[from as usize]) {
+                if !quiet_checks || checks(from, to, piece) {
+                    add_move(&mut moves, from, to, own + piece, opponent_occupancies);
+                }
+            }
+        }
+    }
+
+    if quiet_checks {
+        //Castling checks with the rook, and drops are checks from their square as any other move
+        let mut others = MoveList::new();
+        if checkers == 0 {
+            add_castling_moves(game, &mut others);
+        }
+        if game.variant == Variant::Crazyhouse {
+            add_drops(game, &mut others, check_mask & !all_occupancies);
+        }
+        for m in others.iter() {
+            if if m.is_drop() { check_squares[m.piece() as usize - own] & 1 << m.to_square() != 0 } else { gives_check(game, m) } {
+                moves.add_move(*m);
+            }
+        }
+        return moves;
+    }
+
+    if !captures_only && checkers == 0 {
+        add_castling_moves(game, &mut moves);
+    }
+
+    //A drop can only answer a check by blocking it
+    if !captures_only && game.variant == Variant::Crazyhouse {
+        add_drops(game, &mut moves, check_mask & !all_occupancies);
+    }
+
+    moves
+}
+
+///The squares from which each piece, by piece % 6, attacks the enemy king, the own pieces that
+///would uncover a check from a slider behind them by leaving its line, and the enemy king square
+#[inline(always)]
+fn check_squares(game: &Game, us: Color, own: usize, opponent: usize) -> ([u64; 6], u64, u8) {
+    let king = game.bitboards[opponent + 5].least_significant();
+    let bishop = get_bishop_attack_table(king, game.all_occupancies).to_u64();
+    let rook = get_rook_attack_table(king, game.all_occupancies).to_u64();
+    let targets = [get_pawn_attack_table(king, opposite_color(us)).to_u64(), get_knight_attack_table(king).to_u64(), bishop, rook, bishop | rook, 0];
+
+    //As with pins, with the own pieces in between seen through
+    let own_occupancies = if us == Color::White { game.white_occupancies } else { game.black_occupancies }.to_u64();
+    let opponent_occupancies = Bitboard::from_u64(game.all_occupancies.to_u64() & !own_occupancies);
+    let rooks = (game.bitboards[own + 3].to_u64() | game.bitboards[own + 4].to_u64()) & get_rook_attack_table(king, opponent_occupancies).to_u64();
+    let bishops = (game.bitboards[own + 2].to_u64() | game.bitboards[own + 4].to_u64()) & get_bishop_attack_table(king, opponent_occupancies).to_u64();
+
+    let mut discoverers = 0;
+    for slider in squares(rooks | bishops) {
+        let blockers = between(king, slider) & game.all_occupancies.to_u64();
+        if blockers.count_ones() == 1 && blockers & own_occupancies != 0 {
+            discoverers |= blockers;
+        }
+    }
+
+    (targets, discoverers, king)
+}
+
+///Whether a move checks the opponent king, either with the moved piece or by uncovering a slider behind it
+pub fn gives_check(game: &Game, cmove: &Move) -> bool {
+    let (from, to) = (cmove.from_square(), cmove.to_square());
+    let (us, own, opponent) = if game.active_player == Color::White { (Color::White, 0, 6) } else { (Color::Black, 6, 0) };
+    let king = game.bitboards[opponent + 5].least_significant();
+    let piece = if cmove.promotion() != Piece::None as u8 { cmove.promotion() } else { cmove.piece() } as usize - own;
+
+    //The sliders and occupancy after the move
+    let mut occupancy = (game.all_occupancies.to_u64() & !(1 << from)) | 1 << to;
+    let mut rooks = (game.bitboards[own + 3].to_u64() | game.bitboards[own + 4].to_u64()) & !(1 << from);
+    let mut bishops = (game.bitboards[own + 2].to_u64() | game.bitboards[own + 4].to_u64()) & !(1 << from);
+    if piece == 3 || piece == 4 {
+        rooks |= 1 << to;
+    }
+    if piece == 2 || piece == 4 {
+        bishops |= 1 << to;
+    }
+
+    if cmove.is_enpassant() {
+        occupancy &= !(1 << if us == Color::White { to + 8 } else { to - 8 });
+    }
+    else if cmove.is_castling() {
+        let (rook_from, rook_to) = (game.castling_rook(cmove), if to % 8 == 6 { to - 1 } else { to + 1 });
+        occupancy = (game.all_occupancies.to_u64() & !(1 << from) & !(1 << rook_from)) | 1 << to | 1 << rook_to;
+        rooks = (rooks & !(1 << rook_from)) | 1 << rook_to;
+    }
+
+    let occupancy = Bitboard::from_u64(occupancy);
+    if get_rook_attack_table(king, occupancy).to_u64() & rooks != 0 || get_bishop_attack_table(king, occupancy).to_u64() & bishops != 0 {
+        return true;
+    }
+
+    match piece {
+        0 => get_pawn_attack_table(to, us).to_u64() & 1 << king != 0,
+        1 => get_knight_attack_table(to).to_u64() & 1 << king != 0,
+        _ => false
+    }
+}
+
+///Whether a move that was not generated for this position, like a hash or killer move, could have been.
+///Only the own king may still be left in check
+pub fn is_pseudo_legal(game: &Game, cmove: &Move) -> bool {
+    let (from, to, piece) = (cmove.from_square(), cmove.to_square(), cmove.piece() as usize);
+    let (us, own) = if game.active_player == Color::White { (Color::White, 0) } else { (Color::Black, 6) };
+
+    if cmove.is_drop() {
+        return is_pseudo_legal_drop(game, cmove);
+    }
+
+    if piece < own || piece > own + 5 || !game.bitboards[piece].get_bit(from) {
+        return false;
+    }
+
+    //Rare enough to simply look for it among the generated moves, in Chess960 the king may even stay on its square
+    if cmove.is_castling() {
+        return generate_legal_moves(game, MoveTypes::All).iter().any(|m| m == cmove);
+    }
+
+    if from == to {
+        return false;
+    }
+
+    let own_occupancies = if us == Color::White { game.white_occupancies } else { game.black_occupancies }.to_u64();
+    let opponent_occupancies = if us == Color::White { game.black_occupancies } else { game.white_occupancies }.to_u64();
+    let all_occupancies = game.all_occupancies.to_u64();
+
+    if own_occupancies & 1 << to != 0 {
+        return false;
+    }
+
+    if piece == own {
+        let (forward, start_rank, last_rank): (i8, u8, u8) = if us == Color::White { (-8, 6, 0) } else { (8, 1, 7) };
+        let promotion = cmove.promotion() as usize;
+        if (to / 8 == last_rank) != (promotion != Piece::None as usize) || (promotion != Piece::None as usize && !(own + 1..own + 5).contains(&promotion)) {
+            return false;
+        }
+
+        let attacks = get_pawn_attack_table(from, us).to_u64() & 1 << to != 0;
+        if cmove.is_enpassant() {
+            return cmove.is_capture() && attacks && game.enpassant_square as u8 == to;
+        }
+        if cmove.is_capture() {
+            return attacks && opponent_occupancies & 1 << to != 0;
+        }
+
+        let push = (from as i8 + forward) as u8;
+        if all_occupancies & 1 << push != 0 {
+            return false;
+        }
+        if cmove.is_double_push() {
+            //The Horde's pawns can also double push from the first rank
+            let start = from / 8 == start_rank || (game.variant == Variant::Horde && us == Color::White && from / 8 == 7);
+            return start && to == (push as i8 + forward) as u8 && all_occupancies & 1 << to == 0;
+        }
+        return to == push;
+    }
+
+    if cmove.promotion() != Piece::None as u8 || cmove.is_enpassant() || cmove.is_double_push() || cmove.is_capture() != (opponent_occupancies & 1 << to != 0) {
+        return false;
+    }
+
+    let attacks = match piece - own {
+        1 => get_knight_attack_table(from),
+        2 => get_bishop_attack_table(from, game.all_occupancies),
+        3 => get_rook_attack_table(from, game.all_occupancies),
+        4 => get_queen_attack_table(from, game.all_occupancies),
+        _ => get_king_attack_table(from)
+    }.to_u64();
+
+    attacks & 1 << to != 0
+}
+
+///Adds the castling moves of the side to move, which must not be in check. The king and rooks may start on any file as in Chess960,
+///castling moves the king to the g or c file and the rook next to it, through and into squares that are empty and not attacked
+pub fn add_castling_moves(game: &Game, moves: &mut MoveList) {
+    let (color, own, them, back_rank) = if game.active_player == Color::White { (0, 0, Color::Black, 56) } else { (1, 6, Color::White, 0) };
+    let king = game.bitboards[own + 5].least_significant();
+    let span = |from: u8, to: u8| if from == to { 1 << to } else { between(from, to) | 1 << to };
+
+    for side in 0..2 {
+        let right = color * 2 + side;
+        if game.castling_ability & 1 << right == 0 {
+            continue;
+        }
+
+        let rook = game.castling_rooks[right];
+        let (king_to, rook_to) = if side == 0 { (back_rank + 6, back_rank + 5) } else { (back_rank + 2, back_rank + 3) };
+
+        //Only the king and the castling rook may stand on the squares they pass
+        let others = game.all_occupancies.to_u64() & !(1 << king) & !(1 << rook);
+        if others & (span(king, king_to) | span(rook, rook_to)) != 0 {
+            continue;
+        }
+
+        //On the back rank the rook can't shield the king's path from anything but the other side of the king
+        let occupancy = Bitboard::from_u64(others);
+        if squares(span(king, king_to)).any(|sq| is_attacked(game, sq, occupancy, them)) {
+            continue;
+        }
+
+        moves.add_move(Move::new(king, king_to, (own + 5) as u8, Piece::None as u8, false, false, false, true));
+    }
+}
+
+#[inline(always)]
+fn add_move(moves: &mut MoveList, from: u8, to: u8, piece: usize, opponent_occupancies: u64) {
+    moves.add_move(Move::new(from, to, piece as u8, Piece::None as u8, opponent_occupancies & 1 << to != 0, false, false, false));
+}
+
+#[inline(always)]
+fn add_pawn_move(moves: &mut MoveList, from: u8, to: u8, pawn: usize, promotion: bool, capture: bool) {
+    if promotion {
+        //Queen, knight, rook, bishop as generate_moves orders them
+        for piece in [4, 1, 3, 2] {
+            moves.add_move(Move::new(from, to, pawn as u8, (pawn + piece) as u8, capture, false, false, false));
+        }
+    }
+    else {
+        moves.add_move(Move::new(from, to, pawn as u8, Piece::None as u8, capture, false, false, false));
+    }
+}
+
+///Squares strictly between two squares on a rank, file or diagonal, and none if they don't share one
+#[inline(always)]
+pub fn between(a: u8, b: u8) -> u64 {
+    let (rank_distance, file_distance) = ((a / 8).abs_diff(b / 8), (a % 8).abs_diff(b % 8));
+    let (a_bb, b_bb) = (Bitboard::from_u64(1 << a), Bitboard::from_u64(1 << b));
+
+    if rank_distance == 0 || file_distance == 0 {
+        get_rook_attack_table(a, b_bb).to_u64() & get_rook_attack_table(b, a_bb).to_u64()
+    }
+    else if rank_distance == file_distance {
+        get_bishop_attack_table(a, b_bb).to_u64() & get_bishop_attack_table(b, a_bb).to_u64()
+    }
+    else {
+        0
+    }
+}
+
+///Rooks, bishops and queens attacking the square through the given occupancy
+#[inline(always)]
+fn slider_attackers(game: &Game, square: u8, occupancy: Bitboard, opponent: usize) -> u64 {
+    let rooks = game.bitboards[opponent + 3].to_u64() | game.bitboards[opponent + 4].to_u64();
+    let bishops = game.bitboards[opponent + 2].to_u64() | game.bitboards[opponent + 4].to_u64();
+    (get_rook_attack_table(square, occupancy).to_u64() & rooks) | (get_bishop_attack_table(square, occupancy).to_u64() & bishops)
+}
+
+#[inline(always)]
+fn is_attacked(game: &Game, square: u8, occupancy: Bitboard, by_color: Color) -> bool {
+    let opponent = if by_color == Color::White { 0 } else { 6 };
+    get_pawn_attack_table(square, opposite_color(by_color)).to_u64() & game.bitboards[opponent].to_u64() != 0
+        || get_knight_attack_table(square).to_u64() & game.bitboards[opponent + 1].to_u64() != 0
+        || get_king_attack_table(square).to_u64() & game.bitboards[opponent + 5].to_u64() != 0
+        || slider_attackers(game, square, occupancy, opponent) != 0
+}
+
+#[inline(always)]
+fn squares(mut bits: u64) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let square = bits.trailing_zeros() as u8;
+        bits &= bits - 1;
+        Some(square)
+    })
+}
+
+#[cfg(test)]
+mod legal_move_gen_tests {
+    use super::*;
+
+    fn legal_perft(game: &Game, depth: u8) -> u64 {
+        let moves = generate_legal_moves(game, MoveTypes::All);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.iter().map(|m| {
+            let mut copy = *game;
+            make_move(&mut copy, m);
+            legal_perft(&copy, depth - 1)
+        }).sum()
+    }
+
+    #[test]
+    pub fn matches_pseudo_legal_generation() {
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"] {
+            let mut game = Game::new_from_fen(fen).unwrap();
+            for quiescence in [false, true] {
+                let move_types = || if quiescence { MoveTypes::Quiescence } else { MoveTypes::All };
+                let mut legal: Vec<String> = generate_legal_moves(&game, move_types()).iter().map(|m| m.to_uci()).collect();
+                let mut pseudo: Vec<String> = generate_moves(&mut game, move_types()).legal_values(&game).iter().map(|m| m.to_uci()).collect();
+                legal.sort();
+                pseudo.sort();
+                assert_eq!(legal, pseudo, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    pub fn perft_counts() {
+        assert_eq!(legal_perft(&Game::new_from_start_pos(), 4), 197281);
+        assert_eq!(legal_perft(&Game::new_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(), 3), 97862);
+        assert_eq!(legal_perft(&Game::new_from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(), 5), 674624);
+        assert_eq!(legal_perft(&Game::new_from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap(), 4), 422333);
+        assert_eq!(legal_perft(&Game::new_from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap(), 3), 62379);
+    }
+
+    #[test]
+    pub fn validates_foreign_moves() {
+        let games: Vec<Game> = ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+                                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+                                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                                "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"]
+            .iter().map(|fen| Game::new_from_fen(fen).unwrap()).collect();
+
+        //A move of one position is valid in another exactly when it's generated there
+        for game in &games {
+            let legal = generate_legal_moves(game, MoveTypes::All);
+            for other in &games {
+                for m in generate_legal_moves(other, MoveTypes::All).iter() {
+                    assert_eq!(is_pseudo_legal(game, m) && is_legal(game, m), legal.iter().any(|l| l == m), "{} {}", m.to_uci(), game.to_fen());
+                }
+            }
+        }
+        assert!(!is_pseudo_legal(&games[0], &NULL_MOVE));
+    }
+
+    #[test]
+    pub fn generates_quiet_checks() {
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+                    "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+                    "8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1",
+                    "4k3/8/8/8/4K3/8/4N3/4R3 w - - 0 1",
+                    "8/8/8/3k3p/8/8/4P3/3B2K1 w - - 0 1",
+                    "8/4P3/8/8/8/8/8/4k1K1 w - - 0 1",
+                    "r1b1k2r/ppp2ppp/8/4N3/1b6/2N5/PPP1BPPP/R3K2R[QNPbp] w KQkq - 0 1"] {
+            let game = Game::new_variant_from_fen(fen, if fen.contains('[') { Variant::Crazyhouse } else { Variant::Standard }).unwrap();
+            let mut expected = vec![];
+            for m in generate_legal_moves(&game, MoveTypes::All).iter() {
+                let mut copy = game;
+                make_move(&mut copy, m);
+                let check = copy.is_in_check(copy.active_player);
+                assert_eq!(gives_check(&game, m), check, "{} {}", m.to_uci(), fen);
+                if check && !m.is_capture() {
+                    expected.push(m.to_uci());
+                }
+            }
+
+            let mut checks: Vec<String> = generate_legal_moves(&game, MoveTypes::QuietChecks).iter().map(|m| m.to_uci()).collect();
+            checks.sort();
+            expected.sort();
+            assert_eq!(checks, expected, "{}", fen);
+        }
+
+        //Castling checks with the rook
+        let game = Game::new_from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(generate_legal_moves(&game, MoveTypes::QuietChecks).iter().any(|m| m.is_castling()));
+    }
+
+    #[test]
+    pub fn en_passant_discovered_check() {
+        //Capturing en passant would expose the king on the fifth rank
+        let game = Game::new_from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 2").unwrap();
+        assert!(!generate_legal_moves(&game, MoveTypes::All).iter().any(|m| m.is_enpassant()));
+
+        //Capturing the pawn that gives check is allowed
+        let game = Game::new_from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        assert!(generate_legal_moves(&game, MoveTypes::All).iter().any(|m| m.is_enpassant()));
+    }
+}
@@ -6,14 +6,26 @@ mod move_list;
 mod utilities;
 mod search;
 mod move_generator;
+mod legal_move_generator;
+mod move_picker;
 mod make_move;
 mod perft;
 mod evaluation;
 mod transposition_table;
 mod repetition_table;
+mod nnue;
+mod datagen;
+mod see;
+mod pawn_table;
+mod endgame;
+mod syzygy;
+mod book;
+mod pgn;
+mod epd;
+mod variant;
 
 use core::panic;
-use std::{io::{self}, process, time::SystemTime};
+use std::{io::{self}, process, sync::atomic::Ordering, time::SystemTime};
 
 use game::*;
 
@@ -24,20 +36,47 @@ use move_list::*;
 use utilities::*;
 use search::*;
 use move_generator::*;
+use legal_move_generator::*;
+use move_picker::*;
 use make_move::*;
 use perft::*;
 use evaluation::*;
 use transposition_table::*;
 use repetition_table::*;
+use nnue::*;
+use datagen::*;
+use see::*;
+use pawn_table::*;
+use endgame::*;
+use syzygy::*;
+use book::*;
+use pgn::*;
+use epd::*;
+use variant::*;
+
+//Network loaded at startup if present in the working directory
+const DEFAULT_EVAL_FILE: &str = "jence.nnue";
 
 fn main() {
     let io_receiver = IoWrapper::init();
 
+    if std::path::Path::new(DEFAULT_EVAL_FILE).exists() {
+        if let Err(e) = load_network(DEFAULT_EVAL_FILE) {
+            println!("info string {}", e);
+        }
+    }
+
     let mut game = Game::new_from_start_pos();
 
+    let mut evaluator: Box<dyn Evaluator> = Box::new(ClassicEvaluator::new());
+
     let mut tt = TranspositionTable::new();
 
     let mut repetition_table = RepetitionTable::new();
+
+    let mut book_options = BookOptions::new();
+
+    let mut variant = Variant::Standard;
     
     loop {
         let input = io_receiver.read_line();
@@ -46,11 +85,19 @@ fn main() {
             match split.next().unwrap().to_ascii_lowercase().as_str() {
                 "exit" | "x" | "quit" => { println!(" Exited!"); process::exit(0) },
                 "help" => print_help(),
-                "d" => { game.pretty_print(); }
+                "d" => {
+                    game.pretty_print();
+                    if SAN_OUTPUT.load(Ordering::Relaxed) {
+                        let moves = generate_moves(&mut game, MoveTypes::All).legal_values(&game);
+                        let san: Vec<String> = moves.iter().map(|m| game.move_to_san(m)).collect();
+                        println!("   Moves:      {}\n", san.join(" "));
+                    }
+                }
+                "fen" => println!(" {}", game.to_fen()),
                 "position" => {
                     if !split.peek().is_some() { continue; }
                     repetition_table.clear();
-                    let p = parse_position(input.split_at(9).1.to_string(), &mut repetition_table);
+                    let p = parse_position(input.split_at(9).1.to_string(), variant, &mut repetition_table);
 
                     if p.is_none() {
                         panic!(" Illegal fen string");
@@ -60,6 +107,22 @@ fn main() {
                 },
                 "perft" => {
                     if !split.peek().is_some() { continue; }
+                    if split.peek() == Some(&"hash") {
+                        split.next();
+                        match split.next().and_then(|d| d.parse::<u8>().ok()) {
+                            Some(depth) => go_perft_hashed(depth, game, split.next().and_then(|h| h.parse::<usize>().ok()).unwrap_or(DEFAULT_PERFT_HASH_MB)),
+                            None => println!(" Please provide depth")
+                        }
+                        continue;
+                    }
+                    if split.peek() == Some(&"stats") {
+                        split.next();
+                        match split.next().and_then(|d| d.parse::<u8>().ok()) {
+                            Some(depth) => go_perft_stats(depth, game),
+                            None => println!(" Please provide depth")
+                        }
+                        continue;
+                    }
                     let mut split2 = split.next().unwrap().splitn(2, " ").peekable();
                     if !split2.peek().is_some() { continue; }
                     let pos = split2.next().unwrap().to_string();
@@ -73,6 +136,31 @@ fn main() {
                     }
                     println!(" Done with perft!")
                 },
+                "divide" => {
+                    match split.next().and_then(|d| d.parse::<u8>().ok()) {
+                        Some(depth) => print_divide(&mut game, depth),
+                        None => println!(" Please provide depth")
+                    }
+                },
+                "perftsuite" => {
+                    let path = match split.next() {
+                        Some(path) => path,
+                        None => { println!(" Please provide a perft suite file"); continue; }
+                    };
+                    let depth = split.next().and_then(|d| d.parse::<u8>().ok()).unwrap_or(6);
+                    let hash_mb = split.next().and_then(|h| h.parse::<usize>().ok()).unwrap_or(DEFAULT_PERFT_HASH_MB);
+                    match perft_suite(path, depth, hash_mb) {
+                        Ok(0) => println!(" Suite passed"),
+                        Ok(mismatches) => println!(" Suite FAILED with {} mismatches", mismatches),
+                        Err(e) => println!(" Suite FAILED, {}", e)
+                    }
+                },
+                "perft960" => {
+                    match split.next().and_then(|d| d.parse::<u8>().ok()) {
+                        Some(depth) => chess960_suite(depth, split.next().and_then(|h| h.parse::<usize>().ok()).unwrap_or(DEFAULT_PERFT_HASH_MB)),
+                        None => println!(" Please provide depth")
+                    }
+                },
                 "psuite" => {
                     if split.peek().is_some() {
                         let pos = split.next().unwrap().to_string();
@@ -87,23 +175,118 @@ fn main() {
                 "uci" => {
                     print!("id name JENCE\n");
                     print!("id author Joachim Enggaard Nebel\n");
+                    print!("option name UseNNUE type check default false\n");
+                    print!("option name EvalFile type string default {}\n", DEFAULT_EVAL_FILE);
+                    print!("option name SyzygyPath type string default <empty>\n");
+                    print!("option name OwnBook type check default false\n");
+                    print!("option name BookFile type string default <empty>\n");
+                    print!("option name BookDepth type spin default {} min 1 max 200\n", DEFAULT_BOOK_DEPTH);
+                    print!("option name BookSelection type combo default Weighted var Weighted var Best\n");
+                    print!("option name SANOutput type check default false\n");
+                    print!("option name UCI_Chess960 type check default false\n");
+                    println!("option name UCI_Variant type combo default {} var {}", Variant::Standard.name(), VARIANT_NAMES.join(" var "));
                     print!("uciok\n");
                 },
                 "ucinewgame" | "cleartt" => {
                     repetition_table.clear();
                     tt.clear();
+                    evaluator.clear();
                 },
                 "isready" => print!("readyok\n"),
+                "setoption" => {
+                    let option = parse_setoption(input.split_at(9).1);
+                    if option.is_none() { continue; }
+                    let (name, value) = option.unwrap();
+                    match name.to_ascii_lowercase().as_str() {
+                        "usennue" => {
+                            evaluator = if value == "true" { Box::new(NnueEvaluator::new()) } else { Box::new(ClassicEvaluator::new()) };
+                            if value == "true" && network().is_none() {
+                                println!("info string no network loaded, using classic evaluation");
+                            }
+                        },
+                        "evalfile" => match load_network(&value) {
+                            Ok(()) => println!("info string loaded network {}", value),
+                            Err(e) => println!("info string {}", e)
+                        },
+                        "syzygypath" => match init_tablebases(&value) {
+                            Ok(count) => println!("info string found {} tablebases", count),
+                            Err(e) => println!("info string {}", e)
+                        },
+                        "ownbook" => book_options.own_book = value == "true",
+                        "bookfile" => match Book::load(&value) {
+                            Ok(book) if book.is_empty() => println!("info string {} has no entries", value),
+                            Ok(book) => {
+                                println!("info string loaded {} book entries from {}", book.len(), value);
+                                book_options.book = Some(book);
+                            },
+                            Err(e) => println!("info string {}", e)
+                        },
+                        "bookdepth" => match value.parse::<u16>() {
+                            Ok(depth) => book_options.depth = depth,
+                            Err(_) => println!("info string invalid book depth '{}'", value)
+                        },
+                        "sanoutput" => SAN_OUTPUT.store(value == "true", Ordering::Relaxed),
+                        "uci_chess960" => UCI_CHESS960.store(value == "true", Ordering::Relaxed),
+                        "uci_variant" => match Variant::from_name(&value) {
+                            Some(v) => {
+                                variant = v;
+                                game = Game::new_variant_from_fen(variant.start_fen(), variant).unwrap();
+                            },
+                            None => println!("info string unsupported variant '{}'", value)
+                        },
+                        "bookselection" => book_options.selection = if value.eq_ignore_ascii_case("best") { BookSelection::Best } else { BookSelection::Weighted },
+                        _ => println!("info string unknown option '{}'", name)
+                    }
+                },
                 "go" => {
                     if split.peek().is_none() { continue; }
-                    parse_go(input.split_at(2).1.to_string(), &mut game, &io_receiver, &mut tt, &mut repetition_table)
+                    if let Some(m) = book_options.probe(&game) {
+                        println!("bestmove {}", game.move_to_uci(&m));
+                        continue;
+                    }
+                    parse_go(input.split_at(2).1.to_string(), &mut game, &io_receiver, &mut tt, &mut repetition_table, evaluator.as_mut())
                 },
                 "eval" => {
-                    let result = evaluate(&game);
-                    println!(" {}", result);
+                    if split.peek() == Some(&"trace") {
+                        let mut trace = EvalTrace::new();
+                        evaluate_traced(&game, &mut trace);
+                        trace.print(&game);
+                    } else {
+                        evaluator.prepare(&mut game);
+                        let result = evaluator.evaluate(&game);
+                        println!(" {}", result);
+                    }
                 },
                 "sbench" => {
-                    sbench(&io_receiver)
+                    sbench(&io_receiver, evaluator.as_mut())
+                },
+                "datagen" => {
+                    let games = split.next().and_then(|g| g.parse::<u64>().ok()).unwrap_or(1000);
+                    let nodes = split.next().and_then(|n| n.parse::<u64>().ok()).unwrap_or(5000);
+                    let path = split.next().unwrap_or("data.txt");
+                    datagen(games, nodes, path, split.next());
+                },
+                "makebook" => {
+                    let (pgn_path, book_path) = match (split.next(), split.next()) {
+                        (Some(pgn), Some(book)) => (pgn, book),
+                        _ => { println!(" Please provide a PGN file and a book file"); continue; }
+                    };
+                    let max_ply = split.next().and_then(|p| p.parse::<usize>().ok()).unwrap_or(2 * DEFAULT_BOOK_DEPTH as usize);
+                    let min_games = split.next().and_then(|g| g.parse::<u32>().ok()).unwrap_or(3);
+                    let min_rating = split.next().and_then(|r| r.parse::<u32>().ok()).unwrap_or(0);
+                    makebook(pgn_path, book_path, max_ply, min_games, min_rating);
+                },
+                "epd" => {
+                    let path = match split.next() {
+                        Some(path) => path,
+                        None => { println!(" Please provide an EPD file"); continue; }
+                    };
+                    let limit = match (split.next(), split.next().and_then(|v| v.parse::<i64>().ok())) {
+                        (Some("depth"), Some(depth)) => EpdLimit::Depth(depth as i8),
+                        (Some("time"), Some(ms)) => EpdLimit::Time(ms),
+                        _ => EpdLimit::Time(1000)
+                    };
+                    run_epd(path, limit, evaluator.as_mut());
                 },
                 "move" => {
                     while !split.peek().is_none() {
@@ -124,12 +307,12 @@ fn main() {
     }
 }
 
-fn parse_position(args: String, rep_table: &mut RepetitionTable) -> Option<Game> {
+fn parse_position(args: String, variant: Variant, rep_table: &mut RepetitionTable) -> Option<Game> {
     let pos = args.split(" ").next().unwrap().to_string();
     let rest: String;
     let mut game;
     if pos == "startpos" {
-        game = Game::new_from_start_pos();
+        game = Game::new_variant_from_fen(variant.start_fen(), variant).unwrap();
 
         rest = args.chars().skip(9).collect();
     }
@@ -141,7 +324,7 @@ fn parse_position(args: String, rep_table: &mut RepetitionTable) -> Option<Game>
 
         rest = args.chars().skip(4 + fen.len()).collect();
 
-        let result = Game::new_from_fen(fen.as_str());
+        let result = Game::new_variant_from_fen(fen.as_str(), variant);
         match result {
             Some(g) => game = g,
             None => return None
@@ -168,7 +351,15 @@ fn parse_position(args: String, rep_table: &mut RepetitionTable) -> Option<Game>
     Some(game)
 }
 
-fn parse_go(args: String, game: &mut Game, io_receiver: &IoWrapper, tt: &mut TranspositionTable, rep_table: &mut RepetitionTable){
+fn parse_setoption(args: &str) -> Option<(String, String)> {
+    let rest = args.trim().strip_prefix("name ")?;
+    match rest.split_once(" value ") {
+        Some((name, value)) => Some((name.trim().to_string(), value.trim().to_string())),
+        None => Some((rest.trim().to_string(), String::new()))
+    }
+}
+
+fn parse_go(args: String, game: &mut Game, io_receiver: &IoWrapper, tt: &mut TranspositionTable, rep_table: &mut RepetitionTable, evaluator: &mut dyn Evaluator){
     let mut split = args.split(" ").peekable();
 
     //Load arguments
@@ -255,7 +446,7 @@ fn parse_go(args: String, game: &mut Game, io_receiver: &IoWrapper, tt: &mut Tra
     }
 
     //Run search
-    search(game, depth, time, &io_receiver, tt, rep_table);
+    search(game, depth, time, &io_receiver, tt, rep_table, evaluator);
 }
 
 pub fn read_line() -> String {
@@ -265,7 +456,7 @@ pub fn read_line() -> String {
     input.trim().to_string()
 }
 
-pub fn sbench(io_receiver: &IoWrapper) {
+pub fn sbench(io_receiver: &IoWrapper, evaluator: &mut dyn Evaluator) {
     let poss = [
         Game::new_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(),    //Tricky position
         Game::new_from_fen("rnbqkb1r/pp1p1pPp/8/2p1pP2/1P1P4/3P3P/P1P1P3/RNBQKBNR w KQkq e6 0 1").unwrap(),     //killer position
@@ -283,7 +474,7 @@ pub fn sbench(io_receiver: &IoWrapper) {
     let mut nodes = 0;
     for mut p in poss {
         //p.pretty_print();
-        let result = search(&mut p, depth, -1, &io_receiver, &mut TranspositionTable::new(), &mut RepetitionTable::new());
+        let result = search(&mut p, depth, -1, &io_receiver, &mut TranspositionTable::new(), &mut RepetitionTable::new(), evaluator);
         nodes += result.nodes_visited;
         tt_hits += result.tt_hits;
         if !result.reached_max_ply {
@@ -302,6 +493,20 @@ fn go_perft(depth: u8, mut game: Game, detail: bool) {
     println!(" Found {} moves for depth {} in {}ms", result, depth, duration.as_millis());
 }
 
+fn go_perft_hashed(depth: u8, mut game: Game, hash_mb: usize) {
+    let start = SystemTime::now();
+    let result = perft_hashed(&mut game, depth, &PerftTable::with_size(hash_mb));
+    let duration = start.elapsed().unwrap();
+    println!(" Found {} moves for depth {} in {}ms", result, depth, duration.as_millis());
+}
+
+fn go_perft_stats(depth: u8, mut game: Game) {
+    let start = SystemTime::now();
+    let stats = perft_stats(&mut game, depth);
+    stats.print();
+    println!(" Found {} moves for depth {} in {}ms", stats.nodes, depth, start.elapsed().unwrap().as_millis());
+}
+
 fn psuite() {
     println!(" Performance test running...");
     let mut game = Game::new_from_start_pos();
@@ -429,10 +634,17 @@ fn print_help() {
     println!("  {}", "d                                     - Displays the current board");
     println!("  {}", "position [startpos/fen(fen string)]]  - Sets the game to the given FEN, or to the initial state with \"start\"");
     println!("  {}", "fen                                   - Prints the FEN string for the current position");
-    println!("  {}", "perft (opt) [depth]                   - Counts the number of legal moves at the given depth. Add the simple as \"opt\" to do barebones");
+    println!("  {}", "perft (opt) [depth]                   - Counts the number of legal moves at the given depth. Add the simple as \"opt\" to do barebones, \"stats\" for captures, checks, mates etc. or \"hash\" with a size in MB after the depth to reuse transposed subtrees");
     println!("  {}", "perft! [depth]                        - Does a simple perft for every PLY up to n");
+    println!("  {}", "divide [depth]                        - Counts the nodes at the given depth below every legal move, listed by their UCI move");
+    println!("  {}", "perftsuite [file] (depth) (hash)      - Verifies the \"D1\" to \"D6\" counts of a perft suite EPD file up to the given depth, 6 by default, with a perft hash of \"hash\" MB");
+    println!("  {}", "perft960 [depth] (hash)               - Prints the counts up to the given depth of all 960 Chess960 starting positions as perft suite lines");
     println!("  {}", "unmake/undo                           - Unmakes last move if possible");
     println!("  {}", "make/move [move]                      - Make move with active player. move example: \"h3h4\" in case of promotion, add a Q, R, B or N, so fx. \"a6a7Q\"");
     println!("  {}", "psuite (opt)                          - Performs an extensive performance test with perft on several positions. \"opt\" can be \"long\" for longer test");
-    println!("  {}", "eval                                  - Evaluates the current position, and shows the result");
+    println!("  {}", "datagen [games] [nodes] [file] (pgn)  - Plays fixed-node self-play games and appends \"fen | score | result\" lines to the file, and the games to \"pgn\"");
+    println!("  {}", "makebook [pgn] [book] (plies) (games) (elo) - Builds a Polyglot book from the moves in a PGN file, played in at least \"games\" games by players rated \"elo\"");
+    println!("  {}", "epd [file] (time/depth) (value)       - Searches every position of an EPD test suite for 1000ms or the given time or depth, and reports the solved bm/am/dm positions");
+    println!("  {}", "setoption name [name] value [value]   - Sets a UCI option, fx. \"UseNNUE\", \"EvalFile\", \"SyzygyPath\" or \"UCI_Variant\" (chess, kingofthehill, 3check, atomic, crazyhouse or horde)");
+    println!("  {}", "eval (opt)                            - Evaluates the current position, and shows the result. Add \"trace\" as \"opt\" for a breakdown of every term");
 }
\ No newline at end of file
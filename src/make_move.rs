@@ -1,10 +1,17 @@
 use super::*;
 
-pub fn make_move(game: &mut Game, cmove: &Move) -> bool {
-    make_search_move(game, cmove, &mut RepetitionTable::new())
+pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut RepetitionTable) -> bool {
+    if !make_move(game, cmove) {
+        return false
+    }
+
+    rep_table.insert(game.zobrist_hash);
+
+    true
 }
 
-pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut RepetitionTable) -> bool {
+///Makes the move without recording it for repetition detection. Returns false if the move leaves the king in check
+pub fn make_move(game: &mut Game, cmove: &Move) -> bool {
 
     let from_square = cmove.from_square();
     let to_square   = cmove.to_square();
@@ -20,11 +27,25 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
     if game.enpassant_square != Square::None { game.zobrist_hash ^= ENPASSANT_KEYS[game.enpassant_square as usize] };
     game.zobrist_hash ^= CASTLE_KEYS[game.castling_ability as usize];
 
-    //Update bitboards
-    game.bitboards[piece as usize].unset_bit(from_square);
-    game.zobrist_hash ^= PIECE_KEYS[piece as usize][from_square as usize];
-    game.bitboards[piece as usize].set_bit(to_square);
-    game.zobrist_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+    //Update bitboards, a dropped piece comes out of the pocket instead
+    if cmove.is_drop() {
+        remove_from_pocket(game, if game.active_player == Color::White { 0 } else { 1 }, piece as usize % 6);
+        game.bitboards[piece as usize].set_bit(to_square);
+        game.zobrist_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+        if piece % 6 == Piece::WhitePawn as u8 {
+            game.pawn_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+        }
+    }
+    else {
+        game.bitboards[piece as usize].unset_bit(from_square);
+        game.zobrist_hash ^= PIECE_KEYS[piece as usize][from_square as usize];
+        game.bitboards[piece as usize].set_bit(to_square);
+        game.zobrist_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+        if piece % 6 == Piece::WhitePawn as u8 {
+            game.pawn_hash ^= PIECE_KEYS[piece as usize][from_square as usize];
+            game.pawn_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+        }
+    }
 
     game.all_occupancies.unset_bit(from_square);
     game.all_occupancies.set_bit(to_square);
@@ -38,12 +59,17 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
                 game.black_occupancies.unset_bit(to_square + 8);
                 game.all_occupancies.unset_bit(to_square + 8);
                 game.zobrist_hash ^= PIECE_KEYS[Piece::BlackPawn as usize][to_square as usize + 8];
+                game.pawn_hash ^= PIECE_KEYS[Piece::BlackPawn as usize][to_square as usize + 8];
             }
             else {
                 game.bitboards[Piece::WhitePawn as usize].unset_bit(to_square - 8);
                 game.white_occupancies.unset_bit(to_square - 8);
                 game.all_occupancies.unset_bit(to_square - 8);
                 game.zobrist_hash ^= PIECE_KEYS[Piece::WhitePawn as usize][to_square as usize - 8];
+                game.pawn_hash ^= PIECE_KEYS[Piece::WhitePawn as usize][to_square as usize - 8];
+            }
+            if game.variant == Variant::Crazyhouse {
+                add_to_pocket(game, if game.active_player == Color::White { 0 } else { 1 }, Piece::WhitePawn as usize);
             }
         } else {
             let start;
@@ -63,6 +89,12 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
                 if game.bitboards[piece].get_bit(to_square) {
                     game.bitboards[piece].unset_bit(to_square);
                     game.zobrist_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+                    if piece % 6 == Piece::WhitePawn as usize {
+                        game.pawn_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
+                    }
+                    if game.variant == Variant::Crazyhouse {
+                        pocket_capture(game, to_square, piece);
+                    }
 
                     break;
                 }
@@ -70,8 +102,8 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
         }
     }
 
-    //Check check
-    if game.is_in_check(game.active_player) {
+    //Check check, in Atomic only after the explosion
+    if game.variant != Variant::Atomic && game.is_in_check(game.active_player) {
         return false
     }
     
@@ -84,6 +116,13 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
         game.black_occupancies.set_bit(to_square);
     }
 
+    //Promoted pieces return to the pocket as pawns when captured, so the mark moves along
+    if game.variant == Variant::Crazyhouse && game.promoted.get_bit(from_square) {
+        game.promoted.unset_bit(from_square);
+        game.promoted.set_bit(to_square);
+        game.zobrist_hash ^= PROMOTED_KEYS[from_square as usize] ^ PROMOTED_KEYS[to_square as usize];
+    }
+
     //Increment half moves counter if quiet and reset if pawn
     if piece == Piece::WhitePawn as u8 || piece == Piece::BlackPawn as u8 || capturing{
         game.half_moves = 0;
@@ -99,61 +138,51 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
 
         //Remove pawn
         game.bitboards[piece as usize].unset_bit(to_square);
+        if game.variant == Variant::Crazyhouse {
+            game.promoted.set_bit(to_square);
+            game.zobrist_hash ^= PROMOTED_KEYS[to_square as usize];
+        }
 
         //Zobrist update
         game.zobrist_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
         game.zobrist_hash ^= PIECE_KEYS[promotion as usize][to_square as usize];
+        game.pawn_hash ^= PIECE_KEYS[piece as usize][to_square as usize];
     }
 
-    //Castling
+    //Castling, the king is already on its square. In Chess960 the king and rook may end on each other's squares
     else if castling {
-        match to_square {
-            62 => { //White kingside
-                game.bitboards[Piece::WhiteRook as usize].set_bit_sq(Square::f1);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::WhiteRook as usize][Square::f1 as usize];
-                game.bitboards[Piece::WhiteRook as usize].unset_bit_sq(Square::h1);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::WhiteRook as usize][Square::h1 as usize];
-                game.white_occupancies.set_bit_sq(Square::f1);
-                game.white_occupancies.unset_bit_sq(Square::h1);
-                game.all_occupancies.set_bit_sq(Square::f1);
-                game.all_occupancies.unset_bit_sq(Square::h1);
-            }
-            58 => { //White queenside
-                game.bitboards[Piece::WhiteRook as usize].set_bit_sq(Square::d1);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::WhiteRook as usize][Square::d1 as usize];
-                game.bitboards[Piece::WhiteRook as usize].unset_bit_sq(Square::a1);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::WhiteRook as usize][Square::a1 as usize];
-                game.white_occupancies.set_bit_sq(Square::d1);
-                game.white_occupancies.unset_bit_sq(Square::a1);
-                game.all_occupancies.set_bit_sq(Square::d1);
-                game.all_occupancies.unset_bit_sq(Square::a1);
-            }
-            6 => { //Black kingside
-                game.bitboards[Piece::BlackRook as usize].set_bit_sq(Square::f8);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::BlackRook as usize][Square::f8 as usize];
-                game.bitboards[Piece::BlackRook as usize].unset_bit_sq(Square::h8);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::BlackRook as usize][Square::h8 as usize];
-                game.black_occupancies.set_bit_sq(Square::f8);
-                game.black_occupancies.unset_bit_sq(Square::h8);
-                game.all_occupancies.set_bit_sq(Square::f8);
-                game.all_occupancies.unset_bit_sq(Square::h8);
-            }
-            2 => { //Black queenside
-                game.bitboards[Piece::BlackRook as usize].set_bit_sq(Square::d8);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::BlackRook as usize][Square::d8 as usize];
-                game.bitboards[Piece::BlackRook as usize].unset_bit_sq(Square::a8);
-                game.zobrist_hash ^= PIECE_KEYS[Piece::BlackRook as usize][Square::a8 as usize];
-                game.black_occupancies.set_bit_sq(Square::d8);
-                game.black_occupancies.unset_bit_sq(Square::a8);
-                game.all_occupancies.set_bit_sq(Square::d8);
-                game.all_occupancies.unset_bit_sq(Square::a8);
-            }
-            _ => unreachable!()
+        let (rook, back_rank) = if game.active_player == Color::White { (Piece::WhiteRook as u8, 56) } else { (Piece::BlackRook as u8, 0) };
+        let rook_from = game.castling_rook(cmove);
+        let rook_to = back_rank + if to_square % 8 == 6 { 5 } else { 3 };
+
+        game.bitboards[rook as usize].unset_bit(rook_from);
+        game.zobrist_hash ^= PIECE_KEYS[rook as usize][rook_from as usize];
+        game.bitboards[rook as usize].set_bit(rook_to);
+        game.zobrist_hash ^= PIECE_KEYS[rook as usize][rook_to as usize];
+
+        let occupancies = if game.active_player == Color::White { &mut game.white_occupancies } else { &mut game.black_occupancies };
+        occupancies.unset_bit(rook_from);
+        occupancies.set_bit(rook_to);
+        occupancies.set_bit(to_square);
+        game.all_occupancies.unset_bit(rook_from);
+        game.all_occupancies.set_bit(rook_to);
+        game.all_occupancies.set_bit(to_square);
+    }
+
+    if game.variant == Variant::Atomic {
+        if capturing {
+            explode(game, to_square);
+        }
+
+        //The own king must survive, and may only stay in check if the opponent king exploded
+        let own_king = if game.active_player == Color::White { Piece::WhiteKing } else { Piece::BlackKing };
+        if game.bitboards[own_king as usize].is_empty() || game.is_in_check(game.active_player) {
+            return false
         }
     }
 
-    //Double push
-    if double_push {
+    //Double push, except from the first rank in Horde which allows no en passant
+    if double_push && from_square / 8 != 7 {
         if game.active_player == Color::White {
             game.enpassant_square = SQUARES[to_square as usize + 8];
             game.zobrist_hash ^= ENPASSANT_KEYS[to_square as usize + 8];
@@ -168,7 +197,13 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
     }
 
     //Update castling abililties
-    game.castling_ability &= CASTLING_RIGHTS[to_square as usize] & CASTLING_RIGHTS[from_square as usize];
+    game.castling_ability &= game.castling_rights_kept(to_square) & game.castling_rights_kept(from_square);
+    if piece == Piece::WhiteKing as u8 {
+        game.castling_ability &= !(CastlingAbility::WhiteKingSide as u8 | CastlingAbility::WhiteQueenSide as u8);
+    }
+    else if piece == Piece::BlackKing as u8 {
+        game.castling_ability &= !(CastlingAbility::BlackKingSide as u8 | CastlingAbility::BlackQueenSide as u8);
+    }
     game.zobrist_hash ^= CASTLE_KEYS[game.castling_ability as usize];
 
     //increment fullmoves & switch player
@@ -176,11 +211,17 @@ pub fn make_search_move(game: &mut Game, cmove: &Move, rep_table: &mut Repetitio
         game.full_moves += 1;
     }
 
+    //Count the checks given in Three-check
+    if game.variant == Variant::ThreeCheck && game.is_in_check(opposite_color(game.active_player)) {
+        let side = if game.active_player == Color::White { 0 } else { 1 };
+        let given = game.checks_given[side].min(3) as usize;
+        game.zobrist_hash ^= CHECK_KEYS[side][given] ^ CHECK_KEYS[side][(given + 1).min(3)];
+        game.checks_given[side] += 1;
+    }
+
     game.active_player = opposite_color(game.active_player);
     game.zobrist_hash ^= SIDE_KEY;
 
-    rep_table.insert(game.zobrist_hash);
-
     true
 }
 
@@ -3,11 +3,18 @@ use super::*;
 #[derive(PartialEq)]
 pub enum MoveTypes {
     All,
-    Quiescence
+    Quiescence,
+    ///Moves giving check without capturing
+    QuietChecks
 }
 
 #[inline(always)]
 pub fn generate_moves(game: &mut Game, move_types: MoveTypes) -> MoveList {
+    //Legal moves are pseudo-legal as well
+    if move_types == MoveTypes::QuietChecks {
+        return generate_legal_moves(game, move_types);
+    }
+
     let mut moves = MoveList::new();
     let mut from_sq: u8;
     let mut to_sq:   u8;
@@ -60,7 +67,7 @@ pub fn generate_moves(game: &mut Game, move_types: MoveTypes) -> MoveList {
 
                     //Double push
                     to_sq = (to_sq as i8 - 8) as u8;
-                    if !game.all_occupancies.get_bit(to_sq) && from_sq / 8 == 6 {
+                    if !game.all_occupancies.get_bit(to_sq) && (from_sq / 8 == 6 || (from_sq / 8 == 7 && game.variant == Variant::Horde)) {
                         moves.add_move(Move::new(from_sq, to_sq, Piece::WhitePawn as u8, Piece::None as u8, false, true, false, false));
                     }
                 }
@@ -99,23 +106,6 @@ pub fn generate_moves(game: &mut Game, move_types: MoveTypes) -> MoveList {
                 }
             }
         }
-
-        //Castling kingside
-        if  move_types == MoveTypes::All && game.castling_ability & (CastlingAbility::WhiteKingSide as u8) != 0 &&              //castling ability
-            (game.all_occupancies.and(Bitboard::from_u64(6917529027641081856))).is_empty() &&   //f1 and g1 are free. 6917529027641081856 is f1 and g1 set
-            !game.is_square_attacked(Square::e1 as u8, Color::Black) &&                         //e1 is notunder attack
-            !game.is_square_attacked(Square::f1 as u8, Color::Black) {                          //f1 is not under attack
-
-                moves.add_move(Move::new(Square::e1 as u8, Square::g1 as u8, Piece::WhiteKing as u8, Piece::None as u8, false, false, false, true))
-        }
-        //Castling queen
-        if  move_types == MoveTypes::All && game.castling_ability & (CastlingAbility::WhiteQueenSide as u8) != 0 &&             //castling ability
-            (game.all_occupancies.and(Bitboard::from_u64(1008806316530991104))).is_empty() &&   //d1, c1 and b1 are free. 1008806316530991104 is f1 and g1 set
-            !game.is_square_attacked(Square::e1 as u8, Color::Black) &&                         //e1 is notunder attack
-            !game.is_square_attacked(Square::d1 as u8, Color::Black) {                          //d1 is not under attack
-
-                moves.add_move(Move::new(Square::e1 as u8, Square::c1 as u8, Piece::WhiteKing as u8, Piece::None as u8, false, false, false, true))
-        }
     }
     //BLACK
     else {
@@ -185,23 +175,11 @@ pub fn generate_moves(game: &mut Game, move_types: MoveTypes) -> MoveList {
                 }
             }
         }
+    }
 
-        //Castling kingside
-        if  move_types == MoveTypes::All && game.castling_ability & (CastlingAbility::BlackKingSide as u8) != 0 &&              //castling ability
-            (game.all_occupancies.and(Bitboard::from_u64(96))).is_empty() &&                    //f8 and g8 are free. 96 is f1 and g1 set
-            !game.is_square_attacked(Square::e8 as u8, Color::White) &&                         //e8 is notunder attack
-            !game.is_square_attacked(Square::f8 as u8, Color::White) {                          //f8 is not under attack
-
-                moves.add_move(Move::new(Square::e8 as u8, Square::g8 as u8, Piece::BlackKing as u8, Piece::None as u8, false, false, false, true))
-        }
-        //Castling queen
-        if  move_types == MoveTypes::All && game.castling_ability & (CastlingAbility::BlackQueenSide as u8) != 0 &&             //castling ability
-            (game.all_occupancies.and(Bitboard::from_u64(14))).is_empty() &&                    //d8, c8 and b8 are free. 14 is f1 and g1 set
-            !game.is_square_attacked(Square::e8 as u8, Color::White) &&                         //e8 is notunder attack
-            !game.is_square_attacked(Square::d8 as u8, Color::White) {                          //d8 is not under attack
-
-                moves.add_move(Move::new(Square::e8 as u8, Square::c8 as u8, Piece::BlackKing as u8, Piece::None as u8, false, false, false, true))
-        }
+    //Castling, also in Chess960
+    if move_types == MoveTypes::All && game.castling_ability != 0 && !game.is_in_check(game.active_player) {
+        add_castling_moves(game, &mut moves);
     }
 
     //Knight attacks
@@ -322,11 +300,29 @@ pub fn generate_moves(game: &mut Game, move_types: MoveTypes) -> MoveList {
         }
     }
 
+    if move_types == MoveTypes::All && game.variant == Variant::Crazyhouse {
+        add_drops(game, &mut moves, !game.all_occupancies.to_u64());
+    }
+
     moves
 }
 
 #[inline(always)]
 pub fn is_legal(game: &Game, cmove: &Move) -> bool {
+    if game.variant != Variant::Standard {
+        if is_variant_loss(game) {
+            return false;
+        }
+        if game.variant == Variant::Atomic || game.variant == Variant::Horde {
+            return is_legal_by_copy(game, cmove);
+        }
+    }
+
+    //Castling moves are only generated when legal
+    if cmove.is_castling() {
+        return true;
+    }
+
     let from_sq = cmove.from_square();
     let to_sq = cmove.to_square();
     let capture = cmove.is_capture();
@@ -6,7 +6,8 @@ pub struct MoveList {
     count: usize
 }
 
-const MOVE_LIST_SIZE: usize = 256;
+//Room for the drops of a full Crazyhouse pocket on top of the board moves
+pub const MOVE_LIST_SIZE: usize = 512;
 
 impl MoveList {
 
@@ -55,40 +56,6 @@ impl MoveList {
         self.moves.par_iter().take(self.count)
     }
 
-    pub fn bulk_count(&self, game: &mut Game) -> usize {
-        let mut res = 0;
-        for i in 0..self.len() {
-            if is_legal(game, &self.moves[i]){
-                res += 1
-            }
-        }
-        res
-    }
-
-    #[inline(always)]
-    ///Sorts the moves by their score_move() value with insertion sort
-    pub fn sort_moves(&mut self, game: &Game, envir: &mut SearchEnv) {
-        let mut scores = [0; MOVE_LIST_SIZE];
-        for i in 0..self.count {
-            scores[i] = score_move(&game, self.moves[i], envir)
-        }
-        
-        //Unoptimized!
-        for i in 0..self.count {
-            for j in (i + 1)..self.count {
-                if scores[j] > scores[i] {
-                    let score = scores[j];
-                    scores[j] = scores[i];
-                    scores[i] = score;
-
-                    let mov = self.moves[j];
-                    self.moves[j] = self.moves[i];
-                    self.moves[i] = mov;
-                }
-            }
-        }
-    }
-
     #[cfg(test)]
     pub fn contains(&self, cmove: &Move) -> bool {
         return self.moves.contains(cmove)
@@ -0,0 +1,313 @@
+use super::*;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    FirstKiller,
+    SecondKiller,
+    CounterMove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    GenerateQuietChecks,
+    QuietChecks,
+    Done
+}
+
+///Hands out the moves of a node one at a time, generating each stage only when the previous one is used up,
+///so that a beta cut-off skips the generation and sorting of the remaining moves
+pub struct MovePicker {
+    stage: Stage,
+    quiescence: bool,
+    quiet_checks: bool,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    counter_move: Option<Move>,
+    moves: [Move; MOVE_LIST_SIZE],
+    scores: [i32; MOVE_LIST_SIZE],
+    count: usize,
+    index: usize,
+    bad_captures: [Move; MOVE_LIST_SIZE],
+    bad_count: usize,
+    bad_index: usize
+}
+
+impl MovePicker {
+    ///Picker for the main search. The hash move, killers and counter move are only played if they are legal here
+    pub fn new(game: &Game, hash_move: Option<Move>, envir: &SearchEnv) -> Self {
+        let ply = envir.ply as usize;
+        let counter_move = envir.previous_move().and_then(|p| envir.counter_moves[p.piece() as usize][p.to_square() as usize]);
+
+        Self {
+            stage: Stage::HashMove,
+            quiescence: false,
+            quiet_checks: false,
+            hash_move: hash_move.filter(|m| is_valid_move(game, m)),
+            killers: [envir.killer_moves[0][ply], envir.killer_moves[1][ply]],
+            counter_move,
+            moves: [NULL_MOVE; MOVE_LIST_SIZE],
+            scores: [0; MOVE_LIST_SIZE],
+            count: 0,
+            index: 0,
+            bad_captures: [NULL_MOVE; MOVE_LIST_SIZE],
+            bad_count: 0,
+            bad_index: 0
+        }
+    }
+
+    ///Picker for the quiescence search, handing out only the captures that don't lose material,
+    ///followed by the quiet checks that don't lose material if quiet_checks is set
+    pub fn new_quiescence(quiet_checks: bool) -> Self {
+        Self {
+            stage: Stage::GenerateCaptures,
+            quiescence: true,
+            quiet_checks,
+            hash_move: None,
+            killers: [None; 2],
+            counter_move: None,
+            moves: [NULL_MOVE; MOVE_LIST_SIZE],
+            scores: [0; MOVE_LIST_SIZE],
+            count: 0,
+            index: 0,
+            bad_captures: [NULL_MOVE; MOVE_LIST_SIZE],
+            bad_count: 0,
+            bad_index: 0
+        }
+    }
+
+    #[cfg(test)]
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    ///The next move to search, or None when all moves were handed out
+    pub fn next(&mut self, game: &Game, envir: &SearchEnv) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                },
+                Stage::GenerateCaptures => {
+                    let captures = generate_legal_moves(game, MoveTypes::Quiescence);
+                    for i in 0..captures.len() {
+                        let m = captures.get(i);
+                        if Some(m) != self.hash_move {
+                            self.push(m, MVV_LVA[m.piece() as usize][captured_piece(game, &m)]);
+                        }
+                    }
+                    self.stage = Stage::GoodCaptures;
+                },
+                Stage::GoodCaptures => {
+                    match self.pick_best() {
+                        //Losing captures go after the quiet moves
+                        Some(m) if !see(game, &m, 0) => {
+                            self.bad_captures[self.bad_count] = m;
+                            self.bad_count += 1;
+                        },
+                        Some(m) => return Some(m),
+                        None if !self.quiescence => self.stage = Stage::FirstKiller,
+                        None => self.stage = if self.quiet_checks { Stage::GenerateQuietChecks } else { Stage::Done }
+                    }
+                },
+                Stage::FirstKiller => {
+                    self.stage = Stage::SecondKiller;
+                    self.killers[0] = self.killers[0].filter(|m| self.is_valid_quiet(game, m));
+                    if self.killers[0].is_some() {
+                        return self.killers[0];
+                    }
+                },
+                Stage::SecondKiller => {
+                    self.stage = Stage::CounterMove;
+                    self.killers[1] = self.killers[1].filter(|m| Some(*m) != self.killers[0] && self.is_valid_quiet(game, m));
+                    if self.killers[1].is_some() {
+                        return self.killers[1];
+                    }
+                },
+                Stage::CounterMove => {
+                    self.stage = Stage::GenerateQuiets;
+                    self.counter_move = self.counter_move.filter(|m| !self.killers.contains(&Some(*m)) && self.is_valid_quiet(game, m));
+                    if self.counter_move.is_some() {
+                        return self.counter_move;
+                    }
+                },
+                Stage::GenerateQuiets => {
+                    self.count = 0;
+                    self.index = 0;
+                    let moves = generate_legal_moves(game, MoveTypes::All);
+                    for i in 0..moves.len() {
+                        let m = moves.get(i);
+                        if !m.is_capture() && !self.is_special(&m) {
+                            self.push(m, envir.history_moves[m.piece() as usize][m.to_square() as usize]);
+                        }
+                    }
+                    self.stage = Stage::Quiets;
+                },
+                Stage::Quiets => {
+                    match self.pick_best() {
+                        Some(m) => return Some(m),
+                        None => self.stage = Stage::BadCaptures
+                    }
+                },
+                Stage::BadCaptures => {
+                    if self.bad_index < self.bad_count {
+                        self.bad_index += 1;
+                        return Some(self.bad_captures[self.bad_index - 1]);
+                    }
+                    self.stage = Stage::Done;
+                },
+                Stage::GenerateQuietChecks => {
+                    self.count = 0;
+                    self.index = 0;
+                    let checks = generate_legal_moves(game, MoveTypes::QuietChecks);
+                    for i in 0..checks.len() {
+                        let m = checks.get(i);
+                        self.push(m, envir.history_moves[m.piece() as usize][m.to_square() as usize]);
+                    }
+                    self.stage = Stage::QuietChecks;
+                },
+                Stage::QuietChecks => {
+                    match self.pick_best() {
+                        Some(m) if !see(game, &m, 0) => {},
+                        Some(m) => return Some(m),
+                        None => self.stage = Stage::Done
+                    }
+                },
+                Stage::Done => return None
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn push(&mut self, cmove: Move, score: i32) {
+        self.moves[self.count] = cmove;
+        self.scores[self.count] = score;
+        self.count += 1;
+    }
+
+    ///Selection sort step: swaps the best remaining move to the front and hands it out
+    #[inline(always)]
+    fn pick_best(&mut self) -> Option<Move> {
+        if self.index >= self.count {
+            return None;
+        }
+
+        let mut best = self.index;
+        for i in (self.index + 1)..self.count {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+
+        self.index += 1;
+        Some(self.moves[self.index - 1])
+    }
+
+    ///Whether the move was already handed out before the quiet moves
+    fn is_special(&self, cmove: &Move) -> bool {
+        self.hash_move == Some(*cmove) || self.killers.contains(&Some(*cmove)) || self.counter_move == Some(*cmove)
+    }
+
+    fn is_valid_quiet(&self, game: &Game, cmove: &Move) -> bool {
+        !cmove.is_capture() && self.hash_move != Some(*cmove) && is_valid_move(game, cmove)
+    }
+}
+
+///Whether a move from the transposition table, the PV or a killer slot is legal in this position
+#[inline(always)]
+pub fn is_valid_move(game: &Game, cmove: &Move) -> bool {
+    is_pseudo_legal(game, cmove) && is_legal(game, cmove)
+}
+
+///The piece a capture takes, a pawn for en passant
+#[inline(always)]
+fn captured_piece(game: &Game, cmove: &Move) -> usize {
+    let opponent = if game.active_player == Color::White { 6 } else { 0 };
+    (opponent..opponent + 5).find(|bb| game.bitboards[*bb].get_bit(cmove.to_square())).unwrap_or(opponent)
+}
+
+#[cfg(test)]
+mod move_picker_tests {
+    use super::*;
+
+    fn picked_moves(game: &Game, hash_move: Option<Move>, envir: &SearchEnv) -> Vec<(Move, Stage)> {
+        let mut picker = MovePicker::new(game, hash_move, envir);
+        let mut moves = vec![];
+        while let Some(m) = picker.next(game, envir) {
+            moves.push((m, picker.stage()));
+        }
+        moves
+    }
+
+    #[test]
+    pub fn picks_every_move_once() {
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let mut envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8"] {
+            let game = Game::new_from_fen(fen).unwrap();
+            let legal = generate_legal_moves(&game, MoveTypes::All);
+            let quiets: Vec<Move> = legal.iter().filter(|m| !m.is_capture()).copied().collect();
+
+            //An illegal hash move, a quiet killer, a capture as killer and a quiet counter move
+            envir.killer_moves[0][0] = Some(quiets[quiets.len() - 1]);
+            envir.killer_moves[1][0] = legal.iter().find(|m| m.is_capture()).copied();
+            let e2e4 = Move::new(Square::e2 as u8, Square::e4 as u8, Piece::WhitePawn as u8, Piece::None as u8, false, true, false, false);
+            let picked = picked_moves(&game, Some(e2e4), &envir);
+
+            let mut expected: Vec<String> = legal.iter().map(|m| m.to_uci()).collect();
+            let mut uci: Vec<String> = picked.iter().map(|(m, _)| m.to_uci()).collect();
+            expected.sort();
+            uci.sort();
+            assert_eq!(uci, expected, "{}", fen);
+
+            //Good captures with the most valuable victims first, then the killer, then the quiets
+            let first_quiet = picked.iter().position(|(m, _)| !m.is_capture()).unwrap();
+            assert!(picked[..first_quiet].iter().all(|(m, _)| see(&game, m, 0)));
+            assert!(picked[first_quiet] == (quiets[quiets.len() - 1], Stage::SecondKiller));
+            assert!(picked[first_quiet..].iter().skip_while(|(m, _)| !m.is_capture()).all(|(m, stage)| *stage == Stage::BadCaptures && !see(&game, m, 0)));
+
+            //A legal hash move comes first and only once
+            let hash_move = quiets[0];
+            let picked = picked_moves(&game, Some(hash_move), &envir);
+            assert!(picked[0].0 == hash_move);
+            assert_eq!(picked.len(), legal.len());
+        }
+    }
+
+    #[test]
+    pub fn quiescence_picks_good_captures() {
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+
+        //Qxd5 loses the queen to the pawn on e6, exd5 wins a knight
+        let game = Game::new_from_fen("4k3/8/4p3/3n4/4P3/8/3Q4/4K3 w - - 0 1").unwrap();
+        let mut picker = MovePicker::new_quiescence(false);
+        let mut picked = vec![];
+        while let Some(m) = picker.next(&game, &envir) {
+            picked.push(m.to_uci());
+        }
+        assert_eq!(picked, vec!["e4d5"]);
+
+        //Rd8+ also checks, but loses the rook to the knight
+        let game = Game::new_from_fen("6k1/5ppp/2n5/8/8/8/4Q3/3R2K1 w - - 0 1").unwrap();
+        let mut picker = MovePicker::new_quiescence(true);
+        let mut picked = vec![];
+        while let Some(m) = picker.next(&game, &envir) {
+            picked.push(m.to_uci());
+        }
+        assert_eq!(picked, vec!["e2e8"]);
+    }
+}
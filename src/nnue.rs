@@ -0,0 +1,330 @@
+//Efficiently updatable neural network: 768 -> 2x128 -> 1, with the first layer kept in the Accumulator of Game
+//Network files are little-endian i16s: feature weights [768][HIDDEN_SIZE], feature biases [HIDDEN_SIZE], output weights [2 * HIDDEN_SIZE], output bias
+
+use std::{fs, sync::{Arc, RwLock}};
+
+use super::*;
+
+pub const INPUT_SIZE: usize = 768;
+pub const HIDDEN_SIZE: usize = 128;
+
+//Quantization of the feature transformer (QA) and the output layer (QB)
+const QA: i32 = 255;
+const QB: i32 = 64;
+const EVAL_SCALE: i32 = 400;
+
+const NETWORK_BYTES: usize = (INPUT_SIZE * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1) * 2;
+
+//Swapped as a whole by EvalFile, searches keep the network they started with
+static NETWORK: RwLock<Option<Arc<Network>>> = RwLock::new(None);
+
+pub struct Network {
+    feature_weights: [[i16; HIDDEN_SIZE]; INPUT_SIZE],
+    feature_bias: [i16; HIDDEN_SIZE],
+    output_weights: [i16; 2 * HIDDEN_SIZE],
+    output_bias: i16,
+}
+
+///First layer activations for both perspectives, [0] = white, [1] = black
+#[derive(Clone, Copy)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN_SIZE]; 2],
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        Self { values: [[0; HIDDEN_SIZE]; 2] }
+    }
+}
+
+impl Network {
+    fn from_bytes(bytes: &[u8]) -> Option<Box<Self>> {
+        if bytes.len() != NETWORK_BYTES {
+            return None;
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|c| i16::from_le_bytes([c[0], c[1]]));
+
+        let mut network = Box::new(Self {
+            feature_weights: [[0; HIDDEN_SIZE]; INPUT_SIZE],
+            feature_bias: [0; HIDDEN_SIZE],
+            output_weights: [0; 2 * HIDDEN_SIZE],
+            output_bias: 0,
+        });
+
+        for row in network.feature_weights.iter_mut() {
+            for w in row.iter_mut() {
+                *w = values.next()?;
+            }
+        }
+        for b in network.feature_bias.iter_mut() {
+            *b = values.next()?;
+        }
+        for w in network.output_weights.iter_mut() {
+            *w = values.next()?;
+        }
+        network.output_bias = values.next()?;
+
+        Some(network)
+    }
+
+    ///Recomputes both perspectives of the accumulator from scratch
+    pub fn refresh(&self, game: &Game) -> Accumulator {
+        let mut acc = Accumulator { values: [self.feature_bias; 2] };
+
+        for piece in 0..12 {
+            let mut bb = game.bitboards[piece];
+            while !bb.is_empty() {
+                let square = bb.extract_bit();
+                self.add_feature(&mut acc, piece as u8, square);
+            }
+        }
+
+        acc
+    }
+
+    #[inline(always)]
+    pub fn add_feature(&self, acc: &mut Accumulator, piece: u8, square: u8) {
+        let (white, black) = feature_indices(piece, square);
+        for (a, w) in acc.values[0].iter_mut().zip(self.feature_weights[white].iter()) {
+            *a = a.wrapping_add(*w);
+        }
+        for (a, w) in acc.values[1].iter_mut().zip(self.feature_weights[black].iter()) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    #[inline(always)]
+    pub fn remove_feature(&self, acc: &mut Accumulator, piece: u8, square: u8) {
+        let (white, black) = feature_indices(piece, square);
+        for (a, w) in acc.values[0].iter_mut().zip(self.feature_weights[white].iter()) {
+            *a = a.wrapping_sub(*w);
+        }
+        for (a, w) in acc.values[1].iter_mut().zip(self.feature_weights[black].iter()) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+
+    ///Updates the accumulator of `after` from the pieces that differ from `before`, the position the move was made in.
+    ///This covers every kind of move, explosions and drops included
+    pub fn update(&self, before: &Game, after: &mut Game) {
+        for piece in 0..12 {
+            let (old, new) = (before.bitboards[piece].to_u64(), after.bitboards[piece].to_u64());
+
+            let mut removed = Bitboard::from_u64(old & !new);
+            while !removed.is_empty() {
+                self.remove_feature(&mut after.accumulator, piece as u8, removed.extract_bit());
+            }
+            let mut added = Bitboard::from_u64(new & !old);
+            while !added.is_empty() {
+                self.add_feature(&mut after.accumulator, piece as u8, added.extract_bit());
+            }
+        }
+    }
+
+    ///Runs the output layer. The result is in centipawns from the perspective of the side to move
+    pub fn evaluate(&self, acc: &Accumulator, active_player: Color) -> i32 {
+        let (us, them) = if active_player == Color::White { (&acc.values[0], &acc.values[1]) } else { (&acc.values[1], &acc.values[0]) };
+
+        let (us_weights, them_weights) = self.output_weights.split_at(HIDDEN_SIZE);
+        let sum = crelu_dot(us, us_weights) + crelu_dot(them, them_weights);
+
+        (sum + self.output_bias as i32) * EVAL_SCALE / (QA * QB)
+    }
+}
+
+///Input indices of a piece on a square for the white and black perspective
+#[inline(always)]
+fn feature_indices(piece: u8, square: u8) -> (usize, usize) {
+    let piece_type = (piece % 6) as usize;
+    let is_black = (piece >= 6) as usize;
+
+    //Squares are flipped vertically for black, so both sides see their own pieces move "up" the board
+    let white = (is_black * 6 + piece_type) * 64 + square as usize;
+    let black = ((1 - is_black) * 6 + piece_type) * 64 + (square ^ 56) as usize;
+
+    (white, black)
+}
+
+#[inline(always)]
+fn crelu_dot(values: &[i16; HIDDEN_SIZE], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { crelu_dot_avx2(values, weights) };
+        }
+    }
+    crelu_dot_scalar(values, weights)
+}
+
+fn crelu_dot_scalar(values: &[i16; HIDDEN_SIZE], weights: &[i16]) -> i32 {
+    values.iter().zip(weights.iter()).map(|(v, w)| (*v as i32).clamp(0, QA) * *w as i32).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn crelu_dot_avx2(values: &[i16; HIDDEN_SIZE], weights: &[i16]) -> i32 {
+    use core::arch::x86_64::*;
+
+    let zero = _mm256_setzero_si256();
+    let max = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+
+    for i in (0..HIDDEN_SIZE).step_by(16) {
+        let v = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+        let w = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+        let clipped = _mm256_min_epi16(_mm256_max_epi16(v, zero), max);
+
+        //Pairwise i16 * i16 -> i32 products, summed into 8 lanes
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+    }
+
+    let high = _mm256_extracti128_si256(sum, 1);
+    let low = _mm256_castsi256_si128(sum);
+    let sum128 = _mm_add_epi32(high, low);
+    let sum64 = _mm_add_epi32(sum128, _mm_unpackhi_epi64(sum128, sum128));
+    let sum32 = _mm_add_epi32(sum64, _mm_shuffle_epi32(sum64, 1));
+    _mm_cvtsi128_si32(sum32)
+}
+
+///The loaded network, if any
+#[inline(always)]
+pub fn network() -> Option<Arc<Network>> {
+    NETWORK.read().unwrap().clone()
+}
+
+///Loads a network file, replacing the loaded network. Accumulators must be refreshed afterwards
+pub fn load_network(path: &str) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| format!("could not read '{}': {}", path, e))?;
+    let network = Network::from_bytes(&bytes).ok_or(format!("'{}' is not a valid network, expected {} bytes and found {}", path, NETWORK_BYTES, bytes.len()))?;
+
+    set_network(network);
+    Ok(())
+}
+
+pub fn set_network(network: Box<Network>) {
+    *NETWORK.write().unwrap() = Some(Arc::from(network));
+}
+
+///Evaluates with the network, falling back to the classic evaluation if no network is loaded.
+///The network is fetched once per search, so EvalFile takes effect from the next one
+pub struct NnueEvaluator {
+    net: Option<Arc<Network>>,
+}
+
+impl NnueEvaluator {
+    pub fn new() -> Self {
+        Self { net: network() }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&mut self, game: &Game) -> i32 {
+        match &self.net {
+            //The network knows nothing of the variants' win conditions
+            Some(net) if game.variant == Variant::Standard => net.evaluate(&game.accumulator, game.active_player),
+            _ => evaluate(game),
+        }
+    }
+
+    fn prepare(&mut self, game: &mut Game) {
+        self.net = network();
+        if let Some(net) = &self.net {
+            game.accumulator = net.refresh(game);
+        }
+    }
+
+    fn update(&mut self, before: &Game, after: &mut Game) {
+        if let Some(net) = &self.net {
+            net.update(before, after);
+        }
+    }
+}
+
+#[cfg(test)]
+mod nnue_tests {
+    use super::*;
+
+    fn test_network() -> Box<Network> {
+        //Deterministic pseudo random weights
+        let mut state: u32 = 12345;
+        let mut bytes = Vec::with_capacity(NETWORK_BYTES);
+        for _ in 0..NETWORK_BYTES / 2 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            bytes.extend_from_slice(&((state % 64) as i16 - 32).to_le_bytes());
+        }
+        Network::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    pub fn incremental_update_matches_refresh() {
+        let net = test_network();
+
+        //Captures, castling both ways, promotions with and without capture, en passant, Chess960 castling, an explosion and drops
+        let lines = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", Variant::Standard, vec!["e1g1", "e8c8", "e5f7", "b4c3", "d2c3"]),
+            ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", Variant::Standard, vec!["g2h1q", "b7a8n", "c8b6", "c7c8r"]),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", Variant::Standard, vec!["e5f6", "g8f6"]),
+            ("1r2k3/8/8/8/8/8/8/1RK4R w BHb - 0 1", Variant::Standard, vec!["c1h1", "e8b8", "g1g2"]),
+            ("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", Variant::Atomic, vec!["f3e5", "d8h4"]),
+            ("4k3/8/8/3p4/4P3/8/8/4K3[] w - - 0 1", Variant::Crazyhouse, vec!["e4d5", "e8e7", "P@e6"]),
+        ];
+        for (fen, variant, moves) in lines {
+            let mut game = Game::new_variant_from_fen(fen, variant).unwrap();
+            game.accumulator = net.refresh(&game);
+            for m in moves {
+                let m = game.parse_move(m.to_string()).expect(m);
+                let mut copy = game;
+                assert!(make_move(&mut copy, &m));
+                net.update(&game, &mut copy);
+                assert!(copy.accumulator.values == net.refresh(&copy).values, "{} after {}", fen, m.to_uci());
+                game = copy;
+            }
+        }
+    }
+
+    //The only test using the global network, so no other test sees it change
+    #[test]
+    pub fn evaluator_keeps_its_network_until_the_next_search() {
+        set_network(test_network());
+        let mut evaluator = NnueEvaluator::new();
+        let mut game = Game::new_from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1").unwrap();
+        evaluator.prepare(&mut game);
+        let score = evaluator.evaluate(&game);
+        assert!(score != 0);
+
+        //EvalFile replaces the network, which the next search picks up
+        let path = std::env::temp_dir().join("jence_nnue_test.nnue");
+        fs::write(&path, vec![0; NETWORK_BYTES]).unwrap();
+        assert!(load_network(path.to_str().unwrap()).is_ok());
+        fs::remove_file(path).ok();
+        assert_eq!(evaluator.evaluate(&game), score);
+        evaluator.prepare(&mut game);
+        assert_eq!(evaluator.evaluate(&game), 0);
+
+        //A search refreshes the root accumulator with the network it uses
+        set_network(test_network());
+        let result = search_nodes(&mut game, 3, u64::MAX, &mut TranspositionTable::new(), &mut RepetitionTable::new(), &mut evaluator);
+        assert!(result.best_move != NULL_MOVE);
+        assert!(game.accumulator.values == test_network().refresh(&game).values);
+    }
+
+    #[test]
+    pub fn simd_matches_scalar() {
+        let net = test_network();
+        let game = Game::new_from_start_pos();
+        let acc = net.refresh(&game);
+        let (weights, _) = net.output_weights.split_at(HIDDEN_SIZE);
+        assert_eq!(crelu_dot(&acc.values[0], weights), crelu_dot_scalar(&acc.values[0], weights));
+    }
+
+    #[test]
+    pub fn mirrored_position_has_same_evaluation() {
+        let net = test_network();
+        let white = Game::new_from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let black = Game::new_from_fen("4k3/4p3/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(net.evaluate(&net.refresh(&white), Color::White), net.evaluate(&net.refresh(&black), Color::Black));
+    }
+}
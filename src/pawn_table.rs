@@ -0,0 +1,65 @@
+use super::*;
+
+pub const PAWN_TABLE_SIZE: usize = 1 << 16;
+
+///Cached pawn structure evaluation. The score is from white's perspective, and the bitboards are [white, black]
+#[derive(Copy, Clone)]
+pub struct PawnEntry {
+    pub key: u64,
+    pub score: i32,
+    pub passed: [Bitboard; 2],
+}
+
+pub struct PawnHashTable {
+    table: Box<[Option<PawnEntry>]>
+}
+
+impl PawnHashTable {
+    pub fn new() -> Self {
+        Self{table: vec![None; PAWN_TABLE_SIZE].into_boxed_slice()}
+    }
+
+    pub fn record(&mut self, entry: PawnEntry) {
+        self.table[(entry.key % self.table.len() as u64) as usize] = Some(entry);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        match self.table[(key % self.table.len() as u64) as usize] {
+            Some(entry) if entry.key == key => Some(entry),
+            _ => None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.table.iter_mut().for_each(|e| *e = None);
+    }
+}
+
+#[cfg(test)]
+mod pawn_table_tests {
+    use super::*;
+
+    #[test]
+    pub fn incremental_pawn_hash_matches_recomputed() {
+        let mut game = Game::new_from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for mov in ["a2a4", "b4a3", "d5e6", "a3b2", "g2h3", "b2a1q"] {
+            let m = game.parse_move(mov.to_string()).unwrap();
+            assert!(make_move(&mut game, &m));
+            assert_eq!(game.pawn_hash, game.make_pawn_hash(), "after {}", mov);
+        }
+    }
+
+    #[test]
+    pub fn cached_evaluation_matches_uncached() {
+        let mut pawn_table = PawnHashTable::new();
+        for fen in ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"] {
+            let game = Game::new_from_fen(fen).unwrap();
+            //Second call is answered from the table
+            assert_eq!(evaluate_cached(&game, &mut pawn_table), evaluate(&game));
+            assert!(pawn_table.probe(game.pawn_hash).is_some());
+            assert_eq!(evaluate_cached(&game, &mut pawn_table), evaluate(&game));
+        }
+    }
+}
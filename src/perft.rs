@@ -1,44 +1,370 @@
+use std::{fs, sync::atomic::{AtomicU64, Ordering}, time::SystemTime};
+
 use rayon::prelude::*;
 
 use super::*;
 
+pub const DEFAULT_PERFT_HASH_MB: usize = 64;
+
+//Counts are stored above the depth byte
+const MAX_STORED_NODES: u128 = 1 << 56;
+
+///Subtree counts by position and depth, shared between the rayon workers without locks.
+///Every entry holds the key xored with the data, so a torn write fails the key check instead of returning a wrong count
+pub struct PerftTable {
+    table: Box<[(AtomicU64, AtomicU64)]>
+}
+
+impl PerftTable {
+    ///Table of roughly the given size in megabytes
+    pub fn with_size(megabytes: usize) -> Self {
+        let entries = (megabytes * 1_048_576) / std::mem::size_of::<(AtomicU64, AtomicU64)>();
+        Self{table: (0..entries.max(1)).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect()}
+    }
+
+    pub fn probe(&self, hash: u64, depth: u8) -> Option<u128> {
+        let (key, data) = &self.table[(hash % self.table.len() as u64) as usize];
+        let data = data.load(Ordering::Relaxed);
+        if key.load(Ordering::Relaxed) ^ data == hash && data as u8 == depth { Some((data >> 8) as u128) } else { None }
+    }
+
+    pub fn store(&self, hash: u64, depth: u8, nodes: u128) {
+        if nodes >= MAX_STORED_NODES {
+            return;
+        }
+        let (key, data) = &self.table[(hash % self.table.len() as u64) as usize];
+        let new_data = (nodes as u64) << 8 | depth as u64;
+        key.store(hash ^ new_data, Ordering::Relaxed);
+        data.store(new_data, Ordering::Relaxed);
+    }
+}
+
 pub fn perft(game: &mut Game, depth: u8, print: bool) -> u128 {
-    let moves = generate_moves(game, MoveTypes::All);
+    let moves = generate_legal_moves(game, MoveTypes::All);
 
     if depth == 1 {
-        return moves.bulk_count(game) as u128;
+        return moves.len() as u128;
+    }
+
+    let child = |m: &Move| {
+        let mut copy = *game;
+        make_move(&mut copy, m);
+        let r = perft(&mut copy, depth - 1, false);
+
+        if print {
+            println!("{}: {}", game.move_to_uci(m), r)
+        }
+
+        r
+    };
+
+    if depth > 2 { moves.par_iter().map(child).sum() } else { moves.iter().map(child).sum() }
+}
+
+///Perft reusing the counts of transposed subtrees from the table
+pub fn perft_hashed(game: &mut Game, depth: u8, table: &PerftTable) -> u128 {
+    let moves = generate_legal_moves(game, MoveTypes::All);
+
+    if depth <= 1 {
+        return moves.len() as u128;
+    }
+
+    if let Some(nodes) = table.probe(game.zobrist_hash, depth) {
+        return nodes;
+    }
+
+    let child = |m: &Move| {
+        let mut copy = *game;
+        make_move(&mut copy, m);
+        perft_hashed(&mut copy, depth - 1, table)
+    };
+
+    let nodes = if depth > 2 { moves.par_iter().map(child).sum() } else { moves.iter().map(child).sum() };
+    table.store(game.zobrist_hash, depth, nodes);
+
+    nodes
+}
+
+///Leaf counts below every legal move, sorted by the moves in UCI
+pub fn divide(game: &mut Game, depth: u8) -> Vec<(Move, u128)> {
+    let mut moves: Vec<(Move, u128)> = generate_legal_moves(game, MoveTypes::All).par_iter().map(|m| {
+        let mut copy = *game;
+        make_move(&mut copy, m);
+        (*m, if depth <= 1 { 1 } else { perft(&mut copy, depth - 1, false) })
+    }).collect();
+
+    moves.sort_by_key(|(m, _)| game.move_to_uci(m));
+    moves
+}
+
+pub fn print_divide(game: &mut Game, depth: u8) {
+    let moves = divide(game, depth);
+    for (m, count) in &moves {
+        println!("{}: {}", game.move_to_uci(m), count);
+    }
+    println!("
+ Moves: {}
+ Nodes: {}", moves.len(), moves.iter().map(|(_, c)| c).sum::<u128>());
+}
+
+///A position of a perft suite and its expected leaf counts by depth
+pub struct PerftPosition {
+    pub fen: String,
+    pub counts: Vec<(u8, u128)>,
+}
+
+///Parses a perftsuite line like `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400`
+pub fn parse_perft_line(line: &str) -> Option<PerftPosition> {
+    let mut split = line.split(';');
+    let fen = split.next()?.trim();
+    //The move counters are optional
+    let fen = if fen.split_whitespace().count() == 4 { format!("{} 0 1", fen) } else { fen.to_string() };
+    Game::new_from_fen(&fen)?;
+
+    let counts = split.map(|count| {
+        let (depth, nodes) = count.trim().strip_prefix('D')?.split_once(' ')?;
+        Some((depth.parse().ok()?, nodes.trim().parse().ok()?))
+    }).collect::<Option<Vec<(u8, u128)>>>()?;
+
+    Some(PerftPosition { fen, counts })
+}
+
+///Verifies the counts up to max_depth of every position of a perftsuite EPD file in parallel, sharing a hash table of hash_mb megabytes.
+///Returns the number of mismatches, or an error if the file can't be read
+pub fn perft_suite(path: &str, max_depth: u8, hash_mb: usize) -> Result<usize, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read '{}': {}", path, e))?;
+
+    let positions: Vec<PerftPosition> = text.lines().filter(|l| !l.trim().is_empty() && !l.starts_with('#')).filter_map(|line| {
+        let position = parse_perft_line(line);
+        if position.is_none() {
+            println!(" Skipping '{}'", line);
+        }
+        position
+    }).collect();
+
+    println!(" Verifying {} positions up to depth {}...", positions.len(), max_depth);
+    let start = SystemTime::now();
+    let table = PerftTable::with_size(hash_mb);
+
+    let results: Vec<Vec<(u8, u128, u128)>> = positions.par_iter().map(|position| {
+        let game = Game::new_from_fen(&position.fen).unwrap();
+        position.counts.iter().filter(|(depth, _)| *depth <= max_depth && *depth > 0).map(|(depth, expected)| {
+            let mut copy = game;
+            (*depth, *expected, perft_hashed(&mut copy, *depth, &table))
+        }).collect()
+    }).collect();
+
+    let mut mismatches = 0;
+    for (position, result) in positions.iter().zip(results) {
+        for (depth, expected, found) in result.iter().filter(|(_, expected, found)| expected != found) {
+            println!(" MISMATCH {} at depth {}: expected {}, found {}", position.fen, depth, expected, found);
+            mismatches += 1;
+        }
+    }
+
+    println!(" Done in {}ms, {} mismatches", start.elapsed().unwrap().as_millis(), mismatches);
+    Ok(mismatches)
+}
+
+///Prints the counts up to max_depth of all 960 Chess960 starting positions as perftsuite lines,
+///numbered as in Scharnagl's scheme
+pub fn chess960_suite(max_depth: u8, hash_mb: usize) {
+    let start = SystemTime::now();
+    let table = PerftTable::with_size(hash_mb);
+
+    let lines: Vec<String> = (0..960).into_par_iter().map(|number| {
+        let game = Game::new_chess960(number);
+        let counts: String = (1..=max_depth).map(|depth| {
+            let mut copy = game;
+            format!(" ;D{} {}", depth, perft_hashed(&mut copy, depth, &table))
+        }).collect();
+        format!("{}{}", game.to_fen(), counts)
+    }).collect();
+
+    for line in lines {
+        println!("{}", line);
+    }
+    println!(" Done in {}ms", start.elapsed().unwrap().as_millis());
+}
+
+///Counts of the kinds of moves made at the last ply, as in the published perft tables
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct PerftStats {
+    pub nodes: u128,
+    pub captures: u128,
+    pub en_passants: u128,
+    pub castles: u128,
+    pub promotions: u128,
+    pub checks: u128,
+    pub discovered_checks: u128,
+    pub double_checks: u128,
+    pub checkmates: u128,
+}
+
+impl PerftStats {
+    fn add(self, other: Self) -> Self {
+        Self {
+            nodes: self.nodes + other.nodes,
+            captures: self.captures + other.captures,
+            en_passants: self.en_passants + other.en_passants,
+            castles: self.castles + other.castles,
+            promotions: self.promotions + other.promotions,
+            checks: self.checks + other.checks,
+            discovered_checks: self.discovered_checks + other.discovered_checks,
+            double_checks: self.double_checks + other.double_checks,
+            checkmates: self.checkmates + other.checkmates,
+        }
+    }
+
+    pub fn print(&self) {
+        println!(" Nodes:             {}", self.nodes);
+        println!(" Captures:          {}", self.captures);
+        println!(" En passant:        {}", self.en_passants);
+        println!(" Castles:           {}", self.castles);
+        println!(" Promotions:        {}", self.promotions);
+        println!(" Checks:            {}", self.checks);
+        println!(" Discovered checks: {}", self.discovered_checks);
+        println!(" Double checks:     {}", self.double_checks);
+        println!(" Checkmates:        {}", self.checkmates);
     }
+}
+
+///Perft with the breakdown of the moves at the last ply
+pub fn perft_stats(game: &mut Game, depth: u8) -> PerftStats {
+    let moves = generate_legal_moves(game, MoveTypes::All);
+
+    if depth <= 1 {
+        return moves.iter().fold(PerftStats::default(), |stats, m| stats.add(move_stats(game, m)));
+    }
+
+    let child = |m: &Move| {
+        let mut copy = *game;
+        make_move(&mut copy, m);
+        perft_stats(&mut copy, depth - 1)
+    };
 
     if depth > 2 {
-        moves.par_iter().map(|m| {
-            let mut copy = *game;
-    
-            if make_move(&mut copy, &m) {
-                let r = perft(&mut copy, depth - 1, false);
-
-                if print {
-                    println!("{}{}: {}", SQUARE_STRINGS[m.from_square() as usize], SQUARE_STRINGS[m.to_square() as usize], r)
-                }
-    
-                r
-            }
-            else { 0 }
-        }).sum()
+        moves.par_iter().map(child).reduce(PerftStats::default, PerftStats::add)
+    }
+    else {
+        moves.iter().map(child).fold(PerftStats::default(), PerftStats::add)
+    }
+}
+
+fn move_stats(game: &Game, cmove: &Move) -> PerftStats {
+    let mut copy = *game;
+    make_move(&mut copy, cmove);
+
+    let mut stats = PerftStats {
+        nodes: 1,
+        captures: cmove.is_capture() as u128,
+        en_passants: cmove.is_enpassant() as u128,
+        castles: cmove.is_castling() as u128,
+        promotions: (cmove.promotion() != Piece::None as u8) as u128,
+        ..Default::default()
+    };
+
+    let (king, attackers) = if copy.active_player == Color::White {
+        (copy.get_piece_bitboard(Piece::WhiteKing).least_significant(), copy.black_occupancies)
     }
     else {
-        moves.iter().map(|m| {
-            let mut copy = *game;
-    
-            if make_move(&mut copy, &m) {
-                let r = perft(&mut copy, depth - 1, false);
-    
-                if print {
-                    println!("{}{}: {}", SQUARE_STRINGS[m.from_square() as usize], SQUARE_STRINGS[m.to_square() as usize], r)
-                }
-    
-                r
+        (copy.get_piece_bitboard(Piece::BlackKing).least_significant(), copy.white_occupancies)
+    };
+    let mut checkers = copy.attackers_to(king, copy.all_occupancies).and(attackers);
+
+    if !checkers.is_empty() {
+        //The moved piece gives a direct check, the rook when castling
+        let to = cmove.to_square();
+        let direct = if !cmove.is_castling() { to } else if to % 8 == 6 { to - 1 } else { to + 1 };
+
+        stats.checks = 1;
+        stats.discovered_checks = (checkers.to_u64() & !(1 << direct) != 0) as u128;
+        stats.double_checks = (checkers.pop_count() > 1) as u128;
+        stats.checkmates = (generate_legal_moves(&copy, MoveTypes::All).len() == 0) as u128;
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod perft_tests {
+    use super::*;
+
+    #[test]
+    pub fn divide_uses_full_moves() {
+        //Every promotion is its own move
+        let mut game = Game::new_from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap();
+        let moves = divide(&mut game, 1);
+        assert_eq!(moves.len(), 24);
+        assert!(moves.iter().any(|(m, _)| m.to_uci() == "g2g1n"));
+        assert!(moves.iter().any(|(m, _)| m.to_uci() == "g2g1q"));
+
+        let moves = divide(&mut Game::new_from_start_pos(), 3);
+        assert_eq!(moves.iter().map(|(_, c)| c).sum::<u128>(), 8902);
+        assert_eq!(moves[0].0.to_uci(), "a2a3");
+    }
+
+    #[test]
+    pub fn counts_chess960_positions() {
+        //Positions with castling rights for rooks on any file, from the Chess960 perft results
+        for (fen, counts) in [("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189, 326672]),
+                              ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002, 667366]),
+                              ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471, 273318]),
+                              ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", [22, 593, 13440, 382958]),
+                              ("qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9", [29, 899, 26578, 824055])] {
+            let game = Game::new_from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(perft(&mut game.clone(), depth as u8 + 1, false), *count, "{} depth {}", fen, depth + 1);
             }
-            else { 0 }
-        }).sum()
+        }
+    }
+
+    #[test]
+    pub fn verifies_suite() {
+        let position = parse_perft_line("4k3/8/8/8/8/8/8/4K2R w K - ;D1 15 ;D2 66").unwrap();
+        assert_eq!(position.fen, "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert_eq!(position.counts, vec![(1, 15), (2, 66)]);
+        assert!(parse_perft_line("4k3/8/8/8/8/8/8/4K2R w K - ;D1 x").is_none());
+
+        let path = std::env::temp_dir().join("jence_perft_suite.epd");
+        fs::write(&path, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400 ;D3 8902\n\
+                          4k3/8/8/8/8/8/8/4K2R w K - 0 1 ;D1 15 ;D2 67\n").unwrap();
+        assert_eq!(perft_suite(path.to_str().unwrap(), 3, 1), Ok(1));
+        assert_eq!(perft_suite(path.to_str().unwrap(), 1, 1), Ok(0));
+        fs::remove_file(&path).ok();
+        assert!(perft_suite(path.to_str().unwrap(), 1, 1).is_err());
+    }
+
+    #[test]
+    pub fn counts_move_kinds() {
+        let stats = |fen: &str, depth: u8| perft_stats(&mut Game::new_from_fen(fen).unwrap(), depth);
+        let table = |nodes, captures, en_passants, castles, promotions, checks, discovered_checks, double_checks, checkmates| PerftStats {
+            nodes, captures, en_passants, castles, promotions, checks, discovered_checks, double_checks, checkmates
+        };
+
+        assert_eq!(stats("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4), table(197281, 1576, 0, 0, 0, 469, 0, 0, 8));
+        assert_eq!(stats("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3), table(97862, 17102, 45, 3162, 0, 993, 0, 0, 1));
+        assert_eq!(stats("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4), table(43238, 3348, 123, 0, 0, 1680, 106, 0, 17));
+    }
+
+    #[test]
+    pub fn hashed_perft_matches() {
+        let table = PerftTable::with_size(1);
+        for (fen, depth) in [("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3),
+                             ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+                             ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 4)] {
+            let mut game = Game::new_from_fen(fen).unwrap();
+            assert_eq!(perft_hashed(&mut game, depth, &table), perft(&mut game, depth, false));
+        }
+
+        //Only the stored depth is returned
+        table.store(42, 3, 1234);
+        assert_eq!(table.probe(42, 3), Some(1234));
+        assert_eq!(table.probe(42, 4), None);
+        assert_eq!(table.probe(43, 3), None);
+
+        //Even a table of a single entry gives the right counts
+        let mut game = Game::new_from_start_pos();
+        assert_eq!(perft_hashed(&mut game, 4, &PerftTable::with_size(0)), 197281);
     }
-}
\ No newline at end of file
+}
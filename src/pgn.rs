@@ -0,0 +1,397 @@
+use super::*;
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+//Move suffix annotations and their NAGs
+const SUFFIX_NAGS: [(&str, u8); 6] = [("!!", 3), ("??", 4), ("!?", 5), ("?!", 6), ("!", 1), ("?", 2)];
+
+const LINE_WIDTH: usize = 80;
+
+///A move of the movetext with its annotations and the alternatives to it
+pub struct PgnMove {
+    pub cmove: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    //Comments before and after the move
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(cmove: Move, san: String) -> Self {
+        Self { cmove, san, nags: vec![], comment_before: None, comment: None, variations: vec![] }
+    }
+}
+
+///A game read from or written to PGN: its tag pairs, the mainline moves and the result
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+impl PgnGame {
+    ///An empty game with the seven tag roster, starting from the given position
+    pub fn new(start: &Game) -> Self {
+        let mut pgn = Self {
+            tags: [("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"), ("White", "?"), ("Black", "?"), ("Result", "*")]
+                .iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+            moves: vec![],
+            result: "*".to_string(),
+        };
+
+        if start.to_fen() != Game::new_from_start_pos().to_fen() {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", &start.to_fen());
+        }
+
+        pgn
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string()))
+        }
+    }
+
+    pub fn set_result(&mut self, result: &str) {
+        self.result = result.to_string();
+        self.set_tag("Result", result);
+    }
+
+    ///The starting position, from the FEN tag if there is one
+    pub fn start_position(&self) -> Option<Game> {
+        match self.tag("FEN") {
+            Some(fen) => Game::new_from_fen(fen),
+            None => Some(Game::new_from_start_pos())
+        }
+    }
+
+    ///The position after the mainline moves
+    pub fn end_position(&self) -> Option<Game> {
+        let mut game = self.start_position()?;
+        for m in &self.moves {
+            make_move(&mut game, &m.cmove);
+        }
+        Some(game)
+    }
+
+    ///Adds a legal move to the mainline, with an optional comment like an engine evaluation
+    pub fn push_move(&mut self, cmove: Move, comment: Option<String>) {
+        let mut game = self.end_position().unwrap();
+        let mut pgn_move = PgnMove::new(cmove, game.move_to_san(&cmove));
+        pgn_move.comment = comment;
+        self.moves.push(pgn_move);
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            pgn += &format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+        }
+        pgn.push('\n');
+
+        let mut tokens = vec![];
+        if let Some(start) = self.start_position() {
+            write_line(&self.moves, start, &mut tokens);
+        }
+        tokens.push(self.result.clone());
+
+        //Wrap the movetext
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                pgn += &line;
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        pgn += &line;
+        pgn.push('\n');
+
+        pgn
+    }
+}
+
+///Engine evaluation comment like "+0.35/12" or "-M3/20", from white's perspective
+pub fn eval_comment(score: i32, depth: u8) -> String {
+    if score > MATE_BOUND {
+        format!("+M{}/{}", (MATE_VALUE - score) / 2 + 1, depth)
+    }
+    else if score < -MATE_BOUND {
+        format!("-M{}/{}", (MATE_VALUE + score) / 2 + 1, depth)
+    }
+    else {
+        format!("{:+.2}/{}", score as f64 / 100.0, depth)
+    }
+}
+
+fn write_line(line: &[PgnMove], mut game: Game, tokens: &mut Vec<String>) {
+    let mut needs_number = true;
+
+    for m in line {
+        if let Some(comment) = &m.comment_before {
+            tokens.push(format!("{{{}}}", comment));
+        }
+
+        if game.active_player == Color::White {
+            tokens.push(format!("{}.", game.full_moves));
+        }
+        else if needs_number || m.comment_before.is_some() {
+            tokens.push(format!("{}...", game.full_moves));
+        }
+
+        tokens.push(m.san.clone());
+        for nag in &m.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = &m.comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+
+        for variation in &m.variations {
+            let mut variation_tokens = vec![];
+            write_line(variation, game, &mut variation_tokens);
+            if let (Some(first), Some(last)) = (variation_tokens.first().cloned(), variation_tokens.last().cloned()) {
+                let count = variation_tokens.len();
+                variation_tokens[0] = format!("({}", first);
+                variation_tokens[count - 1] = if count == 1 { format!("({})", first) } else { format!("{})", last) };
+                tokens.append(&mut variation_tokens);
+            }
+        }
+
+        needs_number = m.comment.is_some() || !m.variations.is_empty();
+        make_move(&mut game, &m.cmove);
+    }
+}
+
+///Splits a PGN collection into games, with an error for games with illegal moves
+pub fn read_pgn(text: &str) -> Vec<Result<PgnGame, String>> {
+    let mut games = vec![];
+    let mut tags = vec![];
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            //A tag after movetext starts the next game
+            if !movetext.trim().is_empty() {
+                games.push(parse_game(std::mem::take(&mut tags), &movetext));
+                movetext.clear();
+            }
+            if let Some(tag) = parse_tag(line) {
+                tags.push(tag);
+            }
+        }
+        else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    if !tags.is_empty() || !movetext.trim().is_empty() {
+        games.push(parse_game(tags, &movetext));
+    }
+
+    games
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    Open,
+    Close,
+    Result(String),
+}
+
+fn tokenize(movetext: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = movetext.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Comment(chars.by_ref().take_while(|c| *c != '}').collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))),
+            ';' => tokens.push(Token::Comment(chars.by_ref().take_while(|c| *c != '\n').collect::<String>().trim().to_string())),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() => {},
+            _ => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "{}();".contains(*next) {
+                        break;
+                    }
+                    word.push(chars.next().unwrap());
+                }
+
+                if let Some(nag) = word.strip_prefix('$') {
+                    if let Ok(nag) = nag.parse() {
+                        tokens.push(Token::Nag(nag));
+                    }
+                    continue;
+                }
+                if RESULTS.contains(&word.as_str()) {
+                    tokens.push(Token::Result(word));
+                    continue;
+                }
+
+                //Move numbers, possibly glued to the move like "12.e4". Digits alone don't make one, as in "0-0"
+                let number = word.trim_start_matches(|c: char| c.is_ascii_digit());
+                let word = if number.is_empty() || number.starts_with('.') { number.trim_start_matches('.') } else { word.as_str() };
+                let san = word.trim_end_matches(['!', '?']);
+                if !san.is_empty() {
+                    tokens.push(Token::Move(san.to_string()));
+                }
+                if let Some((_, nag)) = SUFFIX_NAGS.iter().find(|(s, _)| *s == &word[san.len()..]) {
+                    tokens.push(Token::Nag(*nag));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_game(tags: Vec<(String, String)>, movetext: &str) -> Result<PgnGame, String> {
+    let result = tags.iter().find(|(n, _)| n == "Result").map(|(_, v)| v.clone()).unwrap_or("*".to_string());
+    let mut pgn = PgnGame { tags, moves: vec![], result };
+
+    let start = pgn.start_position().ok_or("invalid FEN tag")?;
+    let tokens = tokenize(movetext);
+    let mut pos = 0;
+    pgn.moves = parse_line(&tokens, &mut pos, start, &mut pgn.result, 0)?;
+
+    Ok(pgn)
+}
+
+///Parses moves until the end of the variation, validating them against the legal moves. Depth is 0 for the main line
+fn parse_line(tokens: &[Token], pos: &mut usize, mut game: Game, result: &mut String, depth: usize) -> Result<Vec<PgnMove>, String> {
+    let mut line: Vec<PgnMove> = vec![];
+    let mut before_last = game;
+    let mut pending_comment: Option<String> = None;
+
+    while *pos < tokens.len() {
+        let token = &tokens[*pos];
+        *pos += 1;
+
+        match token {
+            Token::Close if depth == 0 => return Err("unmatched ')'".to_string()),
+            Token::Close => return Ok(line),
+            Token::Open => {
+                let variation = parse_line(tokens, pos, before_last, result, depth + 1)?;
+                match line.last_mut() {
+                    Some(last) => last.variations.push(variation),
+                    None => return Err("variation before the first move".to_string())
+                }
+            },
+            Token::Comment(comment) => match line.last_mut() {
+                Some(last) if pending_comment.is_none() => last.comment = Some(match &last.comment {
+                    Some(c) => format!("{} {}", c, comment),
+                    None => comment.clone()
+                }),
+                _ => pending_comment = Some(comment.clone())
+            },
+            Token::Nag(nag) => if let Some(last) = line.last_mut() {
+                last.nags.push(*nag);
+            },
+            Token::Result(r) => *result = r.clone(),
+            Token::Move(san) => {
+                let cmove = game.parse_san(san).ok_or(format!("illegal move '{}' in position {}", san, game.to_fen()))?;
+                let mut pgn_move = PgnMove::new(cmove, game.move_to_san(&cmove));
+                pgn_move.comment_before = pending_comment.take();
+
+                before_last = game;
+                make_move(&mut game, &cmove);
+                line.push(pgn_move);
+            }
+        }
+    }
+
+    if depth > 0 {
+        return Err("unclosed '('".to_string());
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod pgn_tests {
+    use super::*;
+
+    #[test]
+    pub fn reads_games() {
+        let text = "[Event \"Test\"]\n[White \"A\"]\n[Result \"1-0\"]\n\n1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) Nc6 $1 3.Bb5!? 1-0\n\n[Event \"Second\"]\n\n1. d4 d5 2. Qxd5 *\n";
+        let games = read_pgn(text);
+        assert_eq!(games.len(), 2);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("White"), Some("A"));
+        assert_eq!(game.moves.iter().map(|m| m.san.as_str()).collect::<Vec<_>>(), vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(game.moves[0].comment.as_deref(), Some("best by test"));
+        assert_eq!(game.moves[3].nags, vec![1]);
+        assert_eq!(game.moves[4].nags, vec![5]);
+        assert_eq!(game.result, "1-0");
+
+        //2. f4 exf4 3. Nf3, with 2... d5 instead of exf4
+        let variation = &game.moves[2].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[1].variations[0][0].san, "d5");
+
+        assert!(games[1].as_ref().err().unwrap().contains("Qxd5"));
+    }
+
+    #[test]
+    pub fn rejects_unbalanced_variations() {
+        //The moves after a stray ')' were dropped, and an open '(' swallowed the rest of the game
+        assert_eq!(read_pgn("1. e4 e5 ) 2. Nf3 *\n").remove(0).err(), Some("unmatched ')'".to_string()));
+        assert_eq!(read_pgn("1. e4 e5 2. Nf3 (2. f4 exf4 *\n").remove(0).err(), Some("unclosed '('".to_string()));
+        assert_eq!(read_pgn("1. e4 e5 (1... c5) (1... e6) *\n").remove(0).unwrap().moves[1].variations.len(), 2);
+    }
+
+    #[test]
+    pub fn reads_castling_with_zeros() {
+        let text = "1. e4 e5 2. Nf3 Nf6 3. Bc4 Bc5 4.0-0 0-0 5. d3 d6 6. Bg5 Bg4 7. Nc3 Nc6 8. Qd2 Qd7 9. a3 a6 10. Rae1 Rae8 *\n";
+        let game = read_pgn(text).remove(0).unwrap();
+        assert_eq!(game.moves[6].san, "O-O");
+        assert_eq!(game.moves[7].san, "O-O");
+
+        let game = read_pgn("1. d4 d5 2. Nc3 Nc6 3. Bf4 Bf5 4. Qd2 Qd7 5. 0-0-0 0-0-0 *\n").remove(0).unwrap();
+        assert_eq!(game.moves[8].cmove.to_uci(), "e1c1");
+        assert_eq!(game.moves[9].san, "O-O-O");
+    }
+
+    #[test]
+    pub fn writes_games() {
+        let text = "[Event \"Test\"]\n[Result \"1-0\"]\n\n1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bb5 1-0\n";
+        let game = read_pgn(text).remove(0).unwrap();
+        assert_eq!(game.to_pgn(), "[Event \"Test\"]\n[Result \"1-0\"]\n\n1. e4 {best by test} 1... e5 2. Nf3 (2. f4 exf4) 2... Nc6 $1 3. Bb5 1-0\n");
+
+        //Written games read back the same
+        let again = read_pgn(&game.to_pgn()).remove(0).unwrap();
+        assert_eq!(again.to_pgn(), game.to_pgn());
+
+        let mut game = PgnGame::new(&Game::new_from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 40").unwrap());
+        let mut position = game.end_position().unwrap();
+        game.push_move(position.parse_move("a1a8".to_string()).unwrap(), Some(eval_comment(MATE_VALUE - 1, 5)));
+        game.set_result("1-0");
+        assert!(game.to_pgn().contains("[FEN \"7k/8/6K1/8/8/8/8/R7 w - - 0 40\"]"));
+        assert!(game.to_pgn().ends_with("40. Ra8# {+M1/5} 1-0\n"));
+    }
+}
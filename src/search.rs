@@ -1,3 +1,5 @@
+use std::sync::atomic::AtomicBool;
+
 use rand::{Rng};
 
 use super::*;
@@ -5,24 +7,66 @@ use super::*;
 const MAX_PLY: usize = 64;
 const FULL_DEPTH_MOVES: u8 = 4;
 const REDUCTION_LIMIT: u8 = 3;
+const SEE_PRUNING_DEPTH: u8 = 6;
+const SEE_QUIET_MARGIN: i32 = 60;
+const SEE_CAPTURE_MARGIN: i32 = 100;
 pub const MATE_VALUE: i32 = 49000;
 pub const MATE_BOUND: i32 = 48000; //Lower bound for mating score
 const INFINITY: i32 = 50000;
 
 const INPUT_POLL_INTERVAL: u64 = 16383;
 
+///Print the PV in SAN instead of UCI, set by the SANOutput option
+pub static SAN_OUTPUT: AtomicBool = AtomicBool::new(false);
+
 pub fn search_random(game: &mut Game) {
-    let moves = generate_moves(&mut *game, MoveTypes::All);
-    let rand = rand::thread_rng().gen_range(0..moves.len());
-    print!("bestmove {}\n", moves.get(rand).to_uci());
+    match random_legal_move(game) {
+        Some(m) => print!("bestmove {}\n", game.move_to_uci(&m)),
+        None => print!("bestmove {}\n", NULL_MOVE.to_uci())
+    }
+}
+
+///Picks a uniformly random legal move, or None if there are no legal moves
+pub fn random_legal_move(game: &mut Game) -> Option<Move> {
+    let moves = generate_moves(game, MoveTypes::All).legal_values(game);
+    if moves.is_empty() {
+        return None;
+    }
+    Some(moves[rand::thread_rng().gen_range(0..moves.len())])
 }
 
 //Start a search, max_time = -1 for no limit
-pub fn search(game: &mut Game, depth: i8, max_time: i64, io_receiver: &IoWrapper, tt: &mut TranspositionTable, rep_table: &mut RepetitionTable) -> SearchResult {
+pub fn search(game: &mut Game, depth: i8, max_time: i64, io_receiver: &IoWrapper, tt: &mut TranspositionTable, rep_table: &mut RepetitionTable, evaluator: &mut dyn Evaluator) -> SearchResult {
+
+    let mut envir = SearchEnv::new(max_time, Some(io_receiver), tt, rep_table, evaluator);
+
+    //Positions in the tablebases are played by their DTZ, converting within the 50-move rule. The search only looks at that move
+    if in_tablebases(game) {
+        if let Some((m, _)) = probe_root(game) {
+            envir.root_moves.push(m);
+        }
+    }
+
+    let result = iterative_deepening(game, depth, &mut envir);
 
-    let mut envir = SearchEnv::new(max_time, io_receiver, tt, rep_table);
+    print!("bestmove {}\n", game.move_to_uci(&result.best_move));
 
-    let mut score = 0;
+    result
+}
+
+///Searches without printing or listening for input, stopping after roughly max_nodes nodes once depth 1 is done
+pub fn search_nodes(game: &mut Game, depth: i8, max_nodes: u64, tt: &mut TranspositionTable, rep_table: &mut RepetitionTable, evaluator: &mut dyn Evaluator) -> SearchResult {
+
+    let mut envir = SearchEnv::new(-1, None, tt, rep_table, evaluator);
+    envir.max_nodes = max_nodes;
+    envir.silent = true;
+
+    iterative_deepening(game, depth, &mut envir)
+}
+
+pub fn iterative_deepening(game: &mut Game, depth: i8, envir: &mut SearchEnv) -> SearchResult {
+    let mut score;
+    let mut best_score = 0;
 
     let mut alpha = -INFINITY;
     let mut beta  =  INFINITY;
@@ -30,13 +74,19 @@ pub fn search(game: &mut Game, depth: i8, max_time: i64, io_receiver: &IoWrapper
     let mut current_depth: u8 = 1;
     let max_depth = if depth == -1 { MAX_PLY as u8 } else { depth as u8 };
 
+    envir.evaluator.prepare(game);
+
     while current_depth <= max_depth as u8 {
         envir.follow_pv = true;
 
-        score = negamax(game, current_depth as u8, alpha, beta, &mut envir);
+        score = negamax(game, current_depth as u8, alpha, beta, envir);
 
         if envir.stopping { break }
 
+        best_score = score;
+        envir.completed_depth = current_depth;
+        envir.iterations.push((envir.pv_table[0][0], score, envir.start_time.elapsed().unwrap().as_millis()));
+
         //Narrowing aspiration window
         if score <= alpha || score >= beta {
             alpha = -INFINITY;
@@ -50,6 +100,11 @@ pub fn search(game: &mut Game, depth: i8, max_time: i64, io_receiver: &IoWrapper
         alpha = score - 50;
         beta  = score + 50;
 
+        if envir.silent {
+            current_depth += 1;
+            continue;
+        }
+
         if score >= -MATE_VALUE && score < -MATE_BOUND {
             print!("info score mate {} depth {} nodes {} time {} pv ", -(score + MATE_VALUE) / 2 - 1, current_depth, envir.nodes, envir.start_time.elapsed().unwrap().as_millis());
         }
@@ -60,27 +115,21 @@ pub fn search(game: &mut Game, depth: i8, max_time: i64, io_receiver: &IoWrapper
             print!("info score cp {} depth {} nodes {} time {} pv ", score, current_depth, envir.nodes, envir.start_time.elapsed().unwrap().as_millis());
         }
 
-        for i in 0..envir.pv_lengths[0] {
-            print!("{} ", envir.pv_table[0][i].to_uci());
-        }
-        print!("\n");
+        println!("{}", pv_string(game, &envir.pv_table[0][..envir.pv_lengths[0]]));
         
         current_depth += 1;
     }
 
-    print!("bestmove {}\n", envir.pv_table[0][0].to_uci());
-
-    SearchResult::new(envir.pv_table[0][0], envir.nodes, score, current_depth - 1, !envir.stopping, envir.tt_hits)
+    SearchResult::new(envir.pv_table[0][0], envir.nodes, best_score, current_depth - 1, !envir.stopping, envir.tt_hits)
 }
 
-fn enable_pv_scoring(moves: &MoveList, envir: &mut SearchEnv) {
-    envir.follow_pv = false;
-
-    for i in 0..moves.len() {
-        if envir.pv_table[0][envir.ply as usize] == moves.get(i) {
-            envir.score_pv = true;
-            envir.follow_pv = true;
-        }
+///The PV in UCI, or in SAN with the SANOutput option
+fn pv_string(game: &Game, pv: &[Move]) -> String {
+    if SAN_OUTPUT.load(Ordering::Relaxed) {
+        game.line_to_san(pv).join(" ")
+    }
+    else {
+        pv.iter().map(|m| game.move_to_uci(m)).collect::<Vec<_>>().join(" ")
     }
 }
 
@@ -100,22 +149,42 @@ fn negamax(game: &mut Game, depth: u8, alpha: i32, beta: i32, envir: &mut Search
 
     envir.pv_lengths[envir.ply as usize] = envir.ply as usize;
 
+    //Lost by the rules of the variant, like the opponent king on the hill
+    if is_variant_loss(game) {
+        return -MATE_VALUE + envir.ply as i32;
+    }
+
     if envir.ply > 0 && envir.repetition_table.is_now_in_threefold_repetition() {
         return 0;
     }
 
+    //Tablebase probe after captures and pawn moves
+    if envir.ply > 0 && game.half_moves == 0 && in_tablebases(game) {
+        if let Some(wdl) = probe_wdl(game) {
+            score = match wdl {
+                WDL_WIN => TB_WIN - envir.ply as i32,
+                WDL_LOSS => -TB_WIN + envir.ply as i32,
+                _ => 2 * wdl
+            };
+
+            envir.transposition_table.record(game.zobrist_hash, score, depth, HashFlag::Exact, envir.ply, NULL_MOVE);
+
+            return score;
+        }
+    }
+
     //Dont't go on if reached max ply
     if envir.ply >= MAX_PLY as u8 - 1  {
-        return evaluate(&game);
+        return envir.evaluator.evaluate(game);
     }
 
-    if envir.nodes & INPUT_POLL_INTERVAL == 0 {
+    if envir.nodes & INPUT_POLL_INTERVAL == 0 || envir.nodes >= envir.max_nodes {
         envir.poll_input()
     }
 
     if depth == 0 || game.half_moves == 100 {
         //return evaluate(game)
-        return quiescence(game, alpha, beta, envir);
+        return quiescence(game, alpha, beta, envir, true);
     }
 
     let mut hash_flag = HashFlag::Alpha;
@@ -146,6 +215,7 @@ fn negamax(game: &mut Game, depth: u8, alpha: i32, beta: i32, envir: &mut Search
 
         //..., Depth - 1 - R (with R = 2), ...
 
+        envir.move_stack[envir.ply as usize] = NULL_MOVE;
         envir.ply += 1;
 
         score = -negamax(&mut copy, n_depth - 1 - 2, -beta, -beta + 1, envir);
@@ -160,21 +230,33 @@ fn negamax(game: &mut Game, depth: u8, alpha: i32, beta: i32, envir: &mut Search
         }
     }
 
-    let mut moves = generate_moves(game, MoveTypes::All);
+    //Keep following the PV only while its moves can be played, otherwise start with the hash move
+    let pv_move = if envir.follow_pv { Some(envir.pv_table[0][envir.ply as usize]).filter(|m| is_valid_move(game, m)) } else { None };
+    envir.follow_pv = pv_move.is_some();
 
-    if envir.follow_pv {
-        enable_pv_scoring(&moves, envir)
-    }
-
-    moves.sort_moves(game, envir);
+    let hash_move = pv_move.or(envir.transposition_table.probe_move(game.zobrist_hash));
+    let mut picker = MovePicker::new(game, hash_move, envir);
 
     let mut moves_searched = 0;
+    let mut best_move = NULL_MOVE;
+
+    while let Some(m) = picker.next(game, envir) {
 
-    for i in 0..moves.len() {
-        let m = moves.get(i);
+        if envir.ply == 0 && !envir.root_moves.is_empty() && !envir.root_moves.contains(&m) {
+            continue;
+        }
+
+        //SEE pruning, skip moves losing too much material at shallow depths
+        if !is_pv_node && !in_check && envir.ply > 0 && moves_searched > 0 && depth <= SEE_PRUNING_DEPTH && temp_alpha > -MATE_BOUND {
+            let margin = if m.is_capture() { SEE_CAPTURE_MARGIN } else { SEE_QUIET_MARGIN };
+            if !see(game, &m, -margin * depth as i32) {
+                continue;
+            }
+        }
         
         let mut copy = game.clone();
 
+        envir.move_stack[envir.ply as usize] = m;
         envir.ply += 1;
 
         if !make_search_move(&mut copy, &m, &mut envir.repetition_table) { 
@@ -182,6 +264,7 @@ fn negamax(game: &mut Game, depth: u8, alpha: i32, beta: i32, envir: &mut Search
             
             continue;
         }
+        envir.evaluator.update(game, &mut copy);
 
         legal_moves += 1;
 
@@ -227,17 +310,24 @@ fn negamax(game: &mut Game, depth: u8, alpha: i32, beta: i32, envir: &mut Search
         if score > temp_alpha {
             //Insert PV node
             envir.insert_pv_node(m);
+            best_move = m;
 
             //Beta cut-off
             if score >= beta {
-                //Update killer moves
+                //Update killer and counter moves
                 if !m.is_capture() {
-                    envir.killer_moves[1][envir.ply as usize] = envir.killer_moves[0][envir.ply as usize];
-                    envir.killer_moves[0][envir.ply as usize] = Some(m);
+                    if envir.killer_moves[0][envir.ply as usize] != Some(m) {
+                        envir.killer_moves[1][envir.ply as usize] = envir.killer_moves[0][envir.ply as usize];
+                        envir.killer_moves[0][envir.ply as usize] = Some(m);
+                    }
+
+                    if let Some(previous) = envir.previous_move() {
+                        envir.counter_moves[previous.piece() as usize][previous.to_square() as usize] = Some(m);
+                    }
                 }
     
                 //Record TT entry
-                envir.transposition_table.record(game.zobrist_hash, beta, depth, HashFlag::Beta, envir.ply);
+                envir.transposition_table.record(game.zobrist_hash, beta, depth, HashFlag::Beta, envir.ply, m);
     
                 return beta;
             }
@@ -265,50 +355,63 @@ fn negamax(game: &mut Game, depth: u8, alpha: i32, beta: i32, envir: &mut Search
     }
     
     //Record TT entry
-    envir.transposition_table.record(game.zobrist_hash, temp_alpha, depth, hash_flag, envir.ply);
+    envir.transposition_table.record(game.zobrist_hash, temp_alpha, depth, hash_flag, envir.ply, best_move);
 
     temp_alpha
 }
 
+///Searches captures until the position is quiet, and quiet checks too if quiet_checks is set at the first ply.
+///In check every evasion is searched instead, as standing pat would ignore the threat
 #[inline]
-fn quiescence(game: &mut Game, alpha: i32, beta: i32, envir: &mut SearchEnv) -> i32 {
-    if envir.nodes & INPUT_POLL_INTERVAL == 0 {
+fn quiescence(game: &mut Game, alpha: i32, beta: i32, envir: &mut SearchEnv, quiet_checks: bool) -> i32 {
+    if envir.nodes & INPUT_POLL_INTERVAL == 0 || envir.nodes >= envir.max_nodes {
         envir.poll_input()
     }
 
     envir.nodes += 1;
 
-    let eval = evaluate(&game);
+    if is_variant_loss(game) {
+        return -MATE_VALUE + envir.ply as i32;
+    }
 
     //Dont't go on if reached max ply
-    if envir.ply > MAX_PLY as u8 - 1 || game.half_moves == 100 {
-        return eval;
+    if envir.ply >= MAX_PLY as u8 - 1 || game.half_moves == 100 {
+        return envir.evaluator.evaluate(game);
     }
 
+    let in_check = game.is_in_check(game.active_player);
+
     let mut temp_alpha = alpha;
 
-    if eval > temp_alpha {
-        temp_alpha = eval;
+    if !in_check {
+        let eval = envir.evaluator.evaluate(game);
 
-        if eval >= beta {
-            return beta
+        if eval > temp_alpha {
+            temp_alpha = eval;
+
+            if eval >= beta {
+                return beta
+            }
         }
     }
 
-    let mut moves = generate_moves(game, MoveTypes::Quiescence);
-    moves.sort_moves(game, envir);
+    //Losing captures and checks can't raise alpha, so the picker leaves them out
+    let mut picker = if in_check { MovePicker::new(game, None, envir) } else { MovePicker::new_quiescence(quiet_checks) };
 
-    for i in 0..moves.len() {
-        let m = moves.get(i);
+    let mut legal_moves = 0;
 
-        let mut copy = game.clone();
-        if !make_search_move(&mut copy, &m, &mut envir.repetition_table) {
+    while let Some(m) = picker.next(game, envir) {
+        let mut copy = *game;
+        if !make_search_move(&mut copy, &m, envir.repetition_table) {
             continue;
         }
+        envir.evaluator.update(game, &mut copy);
+
+        legal_moves += 1;
         
         envir.ply += 1;
 
-        let score = -quiescence(&mut copy, -beta, -temp_alpha, envir);
+        let score = -quiescence(&mut copy, -beta, -temp_alpha, envir, false);
 
         envir.ply -= 1;
 
@@ -323,54 +426,12 @@ fn quiescence(game: &mut Game, alpha: i32, beta: i32, envir: &mut SearchEnv) ->
         }
     }
 
-    temp_alpha
-}
-
-#[inline(always)]
-pub fn score_move(game: &Game, cmove: Move, envir: &mut SearchEnv) -> i32 {
-    if envir.score_pv {
-        if envir.pv_table[0][envir.ply as usize] == cmove {
-            envir.score_pv = false;
-            return 20000;
-        }
+    //Mate
+    if in_check && legal_moves == 0 {
+        return -MATE_VALUE + envir.ply as i32;
     }
 
-    let to_sq = cmove.to_square();
-    //Captures
-    if cmove.is_capture() {
-        let start;
-        let end;
-        let mut taken = 0;
-        if game.active_player == Color::White {
-            start = Piece::BlackPawn as usize;
-            end = Piece::BlackKing as usize;
-        }
-        else {
-            start = Piece::WhitePawn as usize;
-            end = Piece::WhiteKing as usize;
-        }
-
-        for bb in start..end {
-            if game.bitboards[bb].get_bit(to_sq) {
-                taken = bb;
-                break;
-            }
-        }
-
-        MVV_LVA[cmove.piece() as usize][taken as usize] + 10000
-    }
-
-    //Quiet moves
-    else {
-        if envir.killer_moves[0][envir.ply as usize] == Some(cmove) {
-            9000
-        } else if envir.killer_moves[1][envir.ply as usize] == Some(cmove) {
-            8000
-        }
-        else {
-            envir.history_moves[cmove.piece() as usize][to_sq as usize]
-        }
-    }
+    temp_alpha
 }
 
 pub struct SearchEnv<'a> {
@@ -378,40 +439,66 @@ pub struct SearchEnv<'a> {
     pub ply: u8,
     pub killer_moves: [[Option<Move>; MAX_PLY]; 2],
     pub history_moves: [[i32; 64]; 12],
+    //Quiet move that refuted the previous move, by its piece and target square
+    pub counter_moves: [[Option<Move>; 64]; 12],
+    //Move played at every ply, NULL_MOVE for null moves
+    pub move_stack: [Move; MAX_PLY],
     pub pv_lengths: [usize; MAX_PLY],
     pub pv_table: [[Move; MAX_PLY]; MAX_PLY],
     pub follow_pv: bool,
-    pub score_pv: bool,
     pub stopping: bool,
-    io_receiver: &'a IoWrapper,
+    pub silent: bool,
+    pub completed_depth: u8,
+    //Only these moves are searched at the root unless empty, like the tablebase move
+    pub root_moves: Vec<Move>,
+    //Best move, score and elapsed milliseconds of every completed depth
+    pub iterations: Vec<(Move, i32, u128)>,
+    io_receiver: Option<&'a IoWrapper>,
     pub start_time: SystemTime,
     max_time: i64,
+    pub max_nodes: u64,
     transposition_table: &'a mut TranspositionTable,
     pub tt_hits: u32,
     pub repetition_table: &'a mut RepetitionTable,
+    pub evaluator: &'a mut dyn Evaluator,
 }
 
 impl <'a>SearchEnv<'a> {
-    pub fn new(max_time: i64, io_receiver: &'a IoWrapper, tt: &'a mut TranspositionTable, rep_table: &'a mut RepetitionTable) -> Self {
+    pub fn new(max_time: i64, io_receiver: Option<&'a IoWrapper>, tt: &'a mut TranspositionTable, rep_table: &'a mut RepetitionTable, evaluator: &'a mut dyn Evaluator) -> Self {
         Self{
             nodes: 0,
             ply: 0,
             killer_moves: [[None; MAX_PLY]; 2],
             history_moves: [[0 as i32; 64]; 12],
+            counter_moves: [[None; 64]; 12],
+            move_stack: [NULL_MOVE; MAX_PLY],
             pv_lengths: [0; MAX_PLY],
             pv_table: [[NULL_MOVE; MAX_PLY]; MAX_PLY],
             follow_pv: false,
-            score_pv: false,
             stopping: false,
+            silent: false,
+            completed_depth: 0,
+            root_moves: vec![],
+            iterations: Vec::new(),
             io_receiver: io_receiver,
             start_time: SystemTime::now(),
             max_time: max_time,
+            max_nodes: u64::MAX,
             transposition_table: tt,
             tt_hits: 0,
-            repetition_table: rep_table
+            repetition_table: rep_table,
+            evaluator
         }
     }
 
+    ///The move that led to the current node, if it was not a null move
+    pub fn previous_move(&self) -> Option<Move> {
+        if self.ply == 0 {
+            return None;
+        }
+        Some(self.move_stack[self.ply as usize - 1]).filter(|m| *m != NULL_MOVE)
+    }
+
     pub fn insert_pv_node(&mut self, cmove: Move) {
         let ply = self.ply as usize;
 
@@ -425,9 +512,75 @@ impl <'a>SearchEnv<'a> {
     }
 
     pub fn poll_input(&mut self) {
-        if (self.max_time != -1 && self.start_time.elapsed().unwrap().as_millis() as i64 >= self.max_time) || self.io_receiver.try_read_line().is_some() {
+        if (self.max_time != -1 && self.start_time.elapsed().unwrap().as_millis() as i64 >= self.max_time) ||
+           (self.nodes >= self.max_nodes && self.completed_depth > 0) ||
+           self.io_receiver.is_some_and(|io| io.try_read_line().is_some()) {
             self.stopping = true;
             return;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+
+    #[test]
+    pub fn quiescence_finds_quiet_check_mates() {
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let mut envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+
+        //Back rank mate with Ra8, which is only seen when quiet checks are searched
+        let mut game = Game::new_from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        assert_eq!(quiescence(&mut game, -INFINITY, INFINITY, &mut envir, true), MATE_VALUE - 1);
+        assert!(quiescence(&mut game, -INFINITY, INFINITY, &mut envir, false) < MATE_BOUND);
+
+        //Re8+ can be answered with Rxe8
+        let mut game = Game::new_from_fen("r5k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1").unwrap();
+        assert!(quiescence(&mut game, -INFINITY, INFINITY, &mut envir, true) < MATE_BOUND);
+    }
+
+    #[test]
+    pub fn quiescence_evades_checks() {
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let mut envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+
+        //Fool's mate, checkmated without a legal move
+        let mut game = Game::new_from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(quiescence(&mut game, -INFINITY, INFINITY, &mut envir, false), -MATE_VALUE);
+
+        //Scholar's mate, Qxf7 is a capture giving mate
+        let mut game = Game::new_from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        assert_eq!(quiescence(&mut game, -INFINITY, INFINITY, &mut envir, false), MATE_VALUE - 1);
+
+        //In check by a knight fork the queen is lost, which standing pat would not see
+        let mut game = Game::new_from_fen("4k3/1q5p/3N4/8/8/8/P7/4K3 b - - 0 1").unwrap();
+        assert!(quiescence(&mut game, -INFINITY, INFINITY, &mut envir, false) < -200);
+
+        //The mate is found at depth 1 through the quiescence search of the mated side
+        let mut game = Game::new_from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        envir.silent = true;
+        let result = iterative_deepening(&mut game, 1, &mut envir);
+        assert_eq!(result.best_move.to_uci(), "h5f7");
+        assert_eq!(result.score, MATE_VALUE - 1);
+    }
+
+    #[test]
+    pub fn searches_only_the_root_moves() {
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let mut envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+        envir.silent = true;
+
+        //Qxf7 mates, but only a3 may be searched
+        let mut game = Game::new_from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        envir.root_moves.push(game.parse_move("a2a3".to_string()).unwrap());
+        let result = iterative_deepening(&mut game, 3, &mut envir);
+        assert_eq!(result.best_move.to_uci(), "a2a3");
+        assert!(result.score < MATE_BOUND);
+    }
+}
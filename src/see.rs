@@ -0,0 +1,156 @@
+use super::*;
+
+///Piece values used when resolving exchanges, indexed by piece % 6
+pub const SEE_VALUES: [i32; 6] = [100, 300, 350, 500, 1000, 20000];
+
+///Static exchange evaluation. Returns true if the exchange started by cmove on its destination square
+///gains at least threshold, with both sides always recapturing with their least valuable attacker (x-rays included)
+pub fn see(game: &Game, cmove: &Move, threshold: i32) -> bool {
+    //In Atomic the capturer explodes, so nothing is ever recaptured
+    if cmove.is_castling() || game.variant == Variant::Atomic {
+        return threshold <= 0;
+    }
+
+    let from_sq = cmove.from_square();
+    let to_sq = cmove.to_square();
+
+    let mut occupancy = game.all_occupancies;
+
+    //Value of what is captured by the move itself, and of the piece left standing on the square
+    let mut captured = 0;
+    if cmove.is_enpassant() {
+        captured = SEE_VALUES[0];
+        occupancy.unset_bit(if game.active_player == Color::White { to_sq + 8 } else { to_sq - 8 });
+    }
+    else if cmove.is_capture() {
+        captured = SEE_VALUES[piece_on(game, to_sq) % 6];
+    }
+
+    let mut on_square = SEE_VALUES[cmove.piece() as usize % 6];
+    if cmove.promotion() != Piece::None as u8 {
+        let promoted = SEE_VALUES[cmove.promotion() as usize % 6];
+        captured += promoted - SEE_VALUES[0];
+        on_square = promoted;
+    }
+
+    let mut swap = captured - threshold;
+    if swap < 0 {
+        return false;
+    }
+
+    swap = on_square - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    occupancy.unset_bit(from_sq);
+    occupancy.set_bit(to_sq);
+
+    let bishops = game.bitboards[Piece::WhiteBishop as usize].or(game.bitboards[Piece::BlackBishop as usize])
+                    .or(game.bitboards[Piece::WhiteQueen as usize]).or(game.bitboards[Piece::BlackQueen as usize]);
+    let rooks = game.bitboards[Piece::WhiteRook as usize].or(game.bitboards[Piece::BlackRook as usize])
+                    .or(game.bitboards[Piece::WhiteQueen as usize]).or(game.bitboards[Piece::BlackQueen as usize]);
+
+    let mut attackers = game.attackers_to(to_sq, occupancy);
+    let mut side = game.active_player;
+
+    //True if the side that made the last capture wins the exchange
+    let mut result = true;
+
+    loop {
+        side = opposite_color(side);
+        attackers = attackers.and(occupancy);
+
+        let side_attackers = attackers.and(if side == Color::White { game.white_occupancies } else { game.black_occupancies });
+        if side_attackers.is_empty() {
+            break;
+        }
+
+        result = !result;
+
+        //Least valuable attacker
+        let offset = if side == Color::White { 0 } else { 6 };
+        let piece_type = (0..6).find(|p| !side_attackers.and(game.bitboards[offset + p]).is_empty()).unwrap();
+
+        //The king can only recapture if the other side has nothing left
+        if piece_type == 5 {
+            let other_attackers = attackers.and(if side == Color::White { game.black_occupancies } else { game.white_occupancies });
+            if !other_attackers.is_empty() {
+                result = !result;
+            }
+            break;
+        }
+
+        swap = SEE_VALUES[piece_type] - swap;
+        if swap < result as i32 {
+            break;
+        }
+
+        let attacker = side_attackers.and(game.bitboards[offset + piece_type]).least_significant();
+        occupancy.unset_bit(attacker);
+
+        //Reveal x-ray attackers behind the piece that just captured
+        if piece_type == 0 || piece_type == 2 || piece_type == 4 {
+            attackers = attackers.or(get_bishop_attack_table(to_sq, occupancy).and(bishops));
+        }
+        if piece_type == 3 || piece_type == 4 {
+            attackers = attackers.or(get_rook_attack_table(to_sq, occupancy).and(rooks));
+        }
+    }
+
+    result
+}
+
+///Index of the piece on the square, or Piece::None
+#[inline(always)]
+fn piece_on(game: &Game, square: u8) -> usize {
+    (0..12).find(|p| game.bitboards[*p].get_bit(square)).unwrap_or(Piece::None as usize)
+}
+
+#[cfg(test)]
+mod see_tests {
+    use super::*;
+
+    fn see_bounds(fen: &str, mov: &str, value: i32) {
+        let mut game = Game::new_from_fen(fen).unwrap();
+        let m = game.parse_move(mov.to_string()).unwrap();
+        assert!(see(&game, &m, value), "{} {} should gain at least {}", fen, mov, value);
+        assert!(!see(&game, &m, value + 1), "{} {} should not gain more than {}", fen, mov, value);
+    }
+
+    #[test]
+    pub fn undefended_pawn_is_won() {
+        see_bounds("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5", 100);
+    }
+
+    #[test]
+    pub fn knight_for_pawn_with_xrays() {
+        see_bounds("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5", -200);
+    }
+
+    #[test]
+    pub fn queen_takes_pawn_defended_by_pawn() {
+        see_bounds("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", "d1d5", -900);
+    }
+
+    #[test]
+    pub fn equal_trade() {
+        see_bounds("4k3/8/5n2/3n4/8/4N3/8/4K3 w - - 0 1", "e3d5", 0);
+    }
+
+    #[test]
+    pub fn quiet_move_to_attacked_square() {
+        see_bounds("4k3/8/8/3p4/8/8/8/2R1K3 w - - 0 1", "c1c4", -500);
+        see_bounds("4k3/8/8/3p4/8/8/8/2R1K3 w - - 0 1", "c1c2", 0);
+    }
+
+    #[test]
+    pub fn king_can_not_recapture_defended_piece() {
+        see_bounds("8/8/8/3k4/3p4/8/3R4/3RK3 w - - 0 1", "d2d4", 100);
+    }
+
+    #[test]
+    pub fn promotion_counts_promoted_piece() {
+        see_bounds("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", 900);
+    }
+}
@@ -0,0 +1,1541 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, sync::{Arc, OnceLock, RwLock}};
+
+use super::*;
+
+///Results of a WDL probe, from the perspective of the side to move. Cursed wins and blessed losses are drawn by the 50-move rule
+pub const WDL_LOSS: i32 = -2;
+pub const WDL_BLESSED_LOSS: i32 = -1;
+pub const WDL_DRAW: i32 = 0;
+pub const WDL_CURSED_WIN: i32 = 1;
+pub const WDL_WIN: i32 = 2;
+
+///Score of a tablebase win, below the mate scores
+pub const TB_WIN: i32 = MATE_BOUND - 1000;
+
+const TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+//Flags of every table in a file
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+//Index of the DTZ value map of a WDL result
+const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+
+//Replaced as a whole when SyzygyPath is set again
+static TABLEBASES: RwLock<Option<Arc<Tablebases>>> = RwLock::new(None);
+static ENCODING: OnceLock<Encoding> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ProbeState {
+    Fail,
+    Ok,
+    //The DTZ table only stores the other side to move
+    ChangeStm,
+    //The best move resets the 50-move counter, so the DTZ table can not be trusted
+    ZeroingBestMove,
+}
+
+pub struct Tablebases {
+    paths: Vec<PathBuf>,
+    entries: Vec<TableEntry>,
+    keys: HashMap<u64, usize>,
+    max_pieces: usize,
+}
+
+///A material combination like KRvK, its files are read at the first probe
+struct TableEntry {
+    code: String,
+    key: u64,
+    key2: u64,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    //Pawns of the leading color and of the other color
+    pawn_count: [usize; 2],
+    wdl: OnceLock<Option<TableData>>,
+    dtz: OnceLock<Option<TableData>>,
+}
+
+struct TableData {
+    bytes: Vec<u8>,
+    //[side to move][file of the leading pawn, or 0 without pawns]
+    items: [[PairsData; 4]; 2],
+    map: usize,
+}
+
+///Indexing and decompression information of one table, the offsets point into the file
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    num_blocks: usize,
+    block_size: usize,
+    span: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    map_idx: [u16; 4],
+}
+
+///Lookup tables for turning a position into a table index. Squares are numbered from a1 = 0 like in the files
+struct Encoding {
+    map_pawns: [usize; 64],
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut encoding = Encoding {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        //Squares below the a1-h8 diagonal
+        let mut code = 0;
+        for s in 0..64 {
+            if off_a1h8(s) < 0 {
+                encoding.map_b1h1h7[s] = code;
+                code += 1;
+            }
+        }
+
+        //The a1-d1-d4 triangle, with the diagonal last
+        let mut diagonal = vec![];
+        let mut code = 0;
+        for s in 0..28 {
+            if off_a1h8(s) < 0 && s % 8 <= 3 {
+                encoding.map_a1d1d4[s] = code;
+                code += 1;
+            }
+            else if off_a1h8(s) == 0 && s % 8 <= 3 {
+                diagonal.push(s);
+            }
+        }
+        for s in diagonal {
+            encoding.map_a1d1d4[s] = code;
+            code += 1;
+        }
+
+        //The 462 legal placements of two kings with the first one in the triangle
+        let mut both_on_diagonal = vec![];
+        let mut code = 0;
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if encoding.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    if square_distance(s1 as u8, s2 as u8) <= 1 || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    }
+                    else {
+                        encoding.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            encoding.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        encoding.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                encoding.binomial[k][n] = (if k > 0 { encoding.binomial[k - 1][n - 1] } else { 0 })
+                                        + (if k < n { encoding.binomial[k][n - 1] } else { 0 });
+            }
+        }
+
+        //Pawn squares a2-h7 mapped so the leading pawn has the highest value, nearest the edge and lowest rank first
+        let mut available = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        encoding.map_pawns[sq] = available;
+                        encoding.map_pawns[sq ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    encoding.lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += encoding.binomial[lead_pawns - 1][encoding.map_pawns[sq]];
+                }
+                encoding.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        encoding
+    }
+}
+
+fn encoding() -> &'static Encoding {
+    ENCODING.get_or_init(Encoding::new)
+}
+
+#[inline(always)]
+fn off_a1h8(sq: usize) -> i32 {
+    (sq >> 3) as i32 - (sq & 7) as i32
+}
+
+///Loads the tables found in the given directories, separated like the PATH variable, replacing the loaded tables.
+///An empty path unloads them, and a path without tables keeps them
+pub fn init_tablebases(path: &str) -> Result<usize, String> {
+    if path.is_empty() || path == "<empty>" {
+        *TABLEBASES.write().unwrap() = None;
+        return Ok(0);
+    }
+
+    let mut tablebases = Tablebases {
+        paths: std::env::split_paths(path).collect(),
+        entries: vec![],
+        keys: HashMap::new(),
+        max_pieces: 0,
+    };
+
+    for code in table_codes() {
+        if find_file(&tablebases.paths, &code, TableKind::Wdl).is_none() {
+            continue;
+        }
+        let entry = TableEntry::new(&code);
+        tablebases.max_pieces = tablebases.max_pieces.max(entry.piece_count);
+        tablebases.keys.insert(entry.key, tablebases.entries.len());
+        tablebases.keys.insert(entry.key2, tablebases.entries.len());
+        tablebases.entries.push(entry);
+    }
+
+    let count = tablebases.entries.len();
+    if count == 0 {
+        return Err(format!("no tablebases found in '{}'", path));
+    }
+
+    *TABLEBASES.write().unwrap() = Some(Arc::new(tablebases));
+    Ok(count)
+}
+
+///The largest number of pieces covered by the loaded tables
+pub fn tablebase_pieces() -> usize {
+    TABLEBASES.read().unwrap().as_ref().map_or(0, |t| t.max_pieces)
+}
+
+///If the position can be probed, the tables do not store castling rights
+pub fn in_tablebases(game: &Game) -> bool {
+    game.variant == Variant::Standard && game.castling_ability == 0 && game.all_occupancies.to_u64().count_ones() as usize <= tablebase_pieces()
+}
+
+///Every material combination the tables exist for, the stronger side first like "KRPvKR"
+fn table_codes() -> Vec<String> {
+    let mut codes = vec![];
+    let mut add = |white: &[usize], black: &[usize]| {
+        let side = |pieces: &[usize]| pieces.iter().map(|p| "PNBRQ".as_bytes()[*p] as char).collect::<String>();
+        codes.push(format!("K{}vK{}", side(white), side(black)));
+    };
+
+    for p1 in 0..5 {
+        add(&[p1], &[]);
+
+        for p2 in 0..=p1 {
+            add(&[p1, p2], &[]);
+            add(&[p1], &[p2]);
+
+            for p3 in 0..5 {
+                add(&[p1, p2], &[p3]);
+            }
+
+            for p3 in 0..=p2 {
+                add(&[p1, p2, p3], &[]);
+
+                for p4 in 0..=p3 {
+                    add(&[p1, p2, p3, p4], &[]);
+
+                    for p5 in 0..=p4 {
+                        add(&[p1, p2, p3, p4, p5], &[]);
+                    }
+                    for p5 in 0..5 {
+                        add(&[p1, p2, p3, p4], &[p5]);
+                    }
+                }
+
+                for p4 in 0..5 {
+                    add(&[p1, p2, p3], &[p4]);
+
+                    for p5 in 0..=p4 {
+                        add(&[p1, p2, p3], &[p4, p5]);
+                    }
+                }
+            }
+
+            for p3 in 0..=p1 {
+                for p4 in 0..=(if p1 == p3 { p2 } else { p3 }) {
+                    add(&[p1, p2], &[p3, p4]);
+                }
+            }
+        }
+    }
+
+    codes
+}
+
+fn find_file(paths: &[PathBuf], code: &str, kind: TableKind) -> Option<PathBuf> {
+    let name = format!("{}{}", code, if kind == TableKind::Wdl { ".rtbw" } else { ".rtbz" });
+    paths.iter().map(|p| Path::new(p).join(&name)).find(|p| p.is_file())
+}
+
+impl TableEntry {
+    fn new(code: &str) -> Self {
+        let (white, black) = code.split_once('v').unwrap();
+        let count = |side: &str, piece: char| side.chars().filter(|c| *c == piece).count();
+
+        let has_unique_pieces = "PNBRQ".chars().any(|p| count(white, p) == 1 || count(black, p) == 1);
+
+        //The leading color is the side with fewer pawns, as long as it has any
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        TableEntry {
+            code: code.to_string(),
+            key: signature_from_str(code),
+            key2: signature_from_str(&format!("{}v{}", black, white)),
+            piece_count: code.len() - 1,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            wdl: OnceLock::new(),
+            dtz: OnceLock::new(),
+        }
+    }
+
+    fn data(&self, kind: TableKind, paths: &[PathBuf]) -> Option<&TableData> {
+        let cell = if kind == TableKind::Wdl { &self.wdl } else { &self.dtz };
+        cell.get_or_init(|| {
+            let data = TableData::load(self, paths, kind);
+            if data.is_none() {
+                println!("info string could not load tablebase {}", self.code);
+            }
+            data
+        }).as_ref()
+    }
+}
+
+impl TableData {
+    fn load(entry: &TableEntry, paths: &[PathBuf], kind: TableKind) -> Option<Self> {
+        let mut bytes = fs::read(find_file(paths, &entry.code, kind)?).ok()?;
+        let magic = if kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.len() < 4 || bytes[..4] != magic {
+            return None;
+        }
+        let size = bytes.len();
+
+        //The Huffman decoder reads ahead of the last block
+        bytes.extend_from_slice(&[0; 16]);
+
+        let mut data = TableData { bytes, items: Default::default(), map: 0 };
+        if data.parse(entry, kind)? > size {
+            return None;
+        }
+        Some(data)
+    }
+
+    #[inline(always)]
+    fn get(&self, entry: &TableEntry, kind: TableKind, stm: usize, file: usize) -> &PairsData {
+        &self.items[if kind == TableKind::Wdl { stm % 2 } else { 0 }][if entry.has_pawns { file } else { 0 }]
+    }
+
+    ///Reads the headers of every table in the file, and returns the end offset of the data
+    fn parse(&mut self, entry: &TableEntry, kind: TableKind) -> Option<usize> {
+        let bytes = &self.bytes;
+        let mut pos = 4;
+
+        let flags = read_u8(bytes, pos)?;
+        if entry.has_pawns != (flags & 2 != 0) || (entry.key != entry.key2) != (flags & 1 != 0) {
+            return None;
+        }
+        pos += 1;
+
+        let sides = if kind == TableKind::Wdl && entry.key != entry.key2 { 2 } else { 1 };
+        let files = if entry.has_pawns { 4 } else { 1 };
+        let pawns_on_both_sides = entry.has_pawns && entry.pawn_count[1] > 0;
+
+        for file in 0..files {
+            let first = read_u8(bytes, pos)?;
+            let second = if pawns_on_both_sides { read_u8(bytes, pos + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            pos += 1 + pawns_on_both_sides as usize;
+
+            for k in 0..entry.piece_count {
+                let pieces = read_u8(bytes, pos)?;
+                for (i, side) in self.items.iter_mut().take(sides).enumerate() {
+                    side[file].pieces[k] = if i == 1 { pieces >> 4 } else { pieces & 0xF };
+                }
+                pos += 1;
+            }
+
+            for (i, side) in self.items.iter_mut().take(sides).enumerate() {
+                set_groups(entry, &mut side[file], order[i], file)?;
+            }
+        }
+
+        pos += pos & 1;
+
+        for file in 0..files {
+            for side in self.items.iter_mut().take(sides) {
+                pos = set_sizes(&mut side[file], bytes, pos)?;
+            }
+        }
+
+        if kind == TableKind::Dtz {
+            self.map = pos;
+            for file in 0..files {
+                let d = &mut self.items[0][file];
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & FLAG_WIDE != 0 {
+                    pos += pos & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = ((pos - self.map) / 2 + 1) as u16;
+                        pos += 2 * read_u16(bytes, pos)? as usize + 2;
+                    }
+                }
+                else {
+                    for i in 0..4 {
+                        d.map_idx[i] = (pos - self.map + 1) as u16;
+                        pos += read_u8(bytes, pos)? as usize + 1;
+                    }
+                }
+            }
+            pos += pos & 1;
+        }
+
+        for file in 0..files {
+            for side in self.items.iter_mut().take(sides) {
+                side[file].sparse_index = pos;
+                pos += side[file].sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side in self.items.iter_mut().take(sides) {
+                side[file].block_length = pos;
+                pos += side[file].block_length_size * 2;
+            }
+        }
+
+        for file in 0..files {
+            for side in self.items.iter_mut().take(sides) {
+                pos = (pos + 0x3F) & !0x3F;
+                side[file].data = pos;
+                pos += side[file].num_blocks * side[file].block_size;
+            }
+        }
+
+        Some(pos)
+    }
+
+    ///Turns a stored value into the WDL result or the DTZ in plies
+    fn map_score(&self, entry: &TableEntry, kind: TableKind, file: usize, value: i32, wdl: i32) -> Option<i32> {
+        if kind == TableKind::Wdl {
+            return Some(value - 2);
+        }
+
+        let d = self.get(entry, kind, 0, file);
+        let mut value = value;
+        if d.flags & FLAG_MAPPED != 0 {
+            let index = d.map_idx[WDL_MAP[(wdl + 2) as usize]] as usize + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 { read_u16(&self.bytes, self.map + 2 * index)? as i32 } else { read_u8(&self.bytes, self.map + index)? as i32 };
+        }
+
+        //The tables store moves unless the plies are needed for precision
+        if (wdl == WDL_WIN && d.flags & FLAG_WIN_PLIES == 0) || (wdl == WDL_LOSS && d.flags & FLAG_LOSS_PLIES == 0) || wdl == WDL_CURSED_WIN || wdl == WDL_BLESSED_LOSS {
+            value *= 2;
+        }
+
+        Some(value + 1)
+    }
+}
+
+///Groups the pieces that are encoded together and computes the index factor of each group
+fn set_groups(entry: &TableEntry, d: &mut PairsData, order: [u8; 2], file: usize) -> Option<()> {
+    let enc = encoding();
+
+    //Without pawns the leading group is three unique pieces, or the two kings
+    let mut first_len: i32 = if entry.has_pawns { 0 } else if entry.has_unique_pieces { 3 } else { 2 };
+    let mut n = 0;
+    d.group_len[0] = 1;
+    for i in 1..entry.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        }
+        else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    let pawns_on_both_sides = entry.has_pawns && entry.pawn_count[1] > 0;
+    let mut next = if pawns_on_both_sides { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if pawns_on_both_sides { d.group_len[1] } else { 0 };
+    let mut idx: u64 = 1;
+
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = idx;
+            idx *= if entry.has_pawns { enc.lead_pawns_size[d.group_len[0]][file] } else if entry.has_unique_pieces { 31332 } else { 462 };
+        }
+        else if k == order[1] as usize {
+            d.group_idx[1] = idx;
+            idx *= enc.binomial.get(d.group_len[1])?[48 - d.group_len[0]];
+        }
+        else {
+            d.group_idx[next] = idx;
+            idx *= enc.binomial.get(d.group_len[next])?[free_squares];
+            free_squares = free_squares.checked_sub(d.group_len[next])?;
+            next += 1;
+        }
+        k += 1;
+        if k > TB_PIECES * 2 {
+            return None;
+        }
+    }
+    d.group_idx[n] = idx;
+
+    Some(())
+}
+
+///Reads the block sizes and the Huffman code of a table
+fn set_sizes(d: &mut PairsData, bytes: &[u8], mut pos: usize) -> Option<usize> {
+    d.flags = read_u8(bytes, pos)?;
+    pos += 1;
+
+    //Every position has the same value, stored as the symbol length
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        d.min_sym_len = read_u8(bytes, pos)?;
+        return Some(pos + 1);
+    }
+
+    let tb_size = d.group_idx[d.group_len.iter().position(|l| *l == 0)?];
+
+    d.block_size = 1 << read_u8(bytes, pos)?;
+    d.span = 1 << read_u8(bytes, pos + 1)?;
+    d.sparse_index_size = tb_size.div_ceil(d.span as u64) as usize;
+    let padding = read_u8(bytes, pos + 2)? as usize;
+    d.num_blocks = read_u32(bytes, pos + 3)? as usize;
+    d.block_length_size = d.num_blocks + padding;
+    let max_sym_len = read_u8(bytes, pos + 7)?;
+    d.min_sym_len = read_u8(bytes, pos + 8)?;
+    if d.min_sym_len == 0 || max_sym_len < d.min_sym_len {
+        return None;
+    }
+    pos += 9;
+    d.lowest_sym = pos;
+
+    //Canonical Huffman code: base64[l] is the lowest symbol of length l + min_sym_len, padded to 64 bits
+    let lengths = (max_sym_len - d.min_sym_len) as usize + 1;
+    d.base64 = vec![0; lengths];
+    for i in (0..lengths - 1).rev() {
+        let lowest = read_u16(bytes, d.lowest_sym + 2 * i)? as u64;
+        let next_lowest = read_u16(bytes, d.lowest_sym + 2 * i + 2)? as u64;
+        d.base64[i] = d.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+    }
+    for (i, base) in d.base64.iter_mut().enumerate() {
+        *base = base.checked_shl((64 - i - d.min_sym_len as usize) as u32).unwrap_or(0);
+    }
+    pos += lengths * 2;
+
+    let symbols = read_u16(bytes, pos)? as usize;
+    pos += 2;
+    d.btree = pos;
+
+    //Every symbol is a pair of other symbols, expanding to symlen + 1 values
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for sym in 0..symbols {
+        if !visited[sym] {
+            d.symlen[sym] = set_symlen(d, bytes, sym, &mut visited)?;
+        }
+    }
+
+    Some(pos + symbols * 3 + (symbols & 1))
+}
+
+fn set_symlen(d: &mut PairsData, bytes: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+    visited[sym] = true;
+
+    let right = btree_right(bytes, d.btree, sym)?;
+    if right == 0xFFF {
+        return Some(0);
+    }
+    let left = btree_left(bytes, d.btree, sym)?;
+
+    if !*visited.get(left)? {
+        d.symlen[left] = set_symlen(d, bytes, left, visited)?;
+    }
+    if !*visited.get(right)? {
+        d.symlen[right] = set_symlen(d, bytes, right, visited)?;
+    }
+
+    Some(d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1))
+}
+
+///Finds the value at the given index of a compressed table
+fn decompress_pairs(d: &PairsData, bytes: &[u8], idx: u64) -> Option<i32> {
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        return Some(d.min_sym_len as i32);
+    }
+
+    //The sparse index points to the block of every span'th value, from there we walk to the right block
+    let span = d.span as u64;
+    let k = (idx / span) as usize;
+    let mut block = read_u32(bytes, d.sparse_index + 6 * k)? as usize;
+    let mut offset = read_u16(bytes, d.sparse_index + 6 * k + 4)? as i64;
+    offset += (idx % span) as i64 - (span / 2) as i64;
+
+    while offset < 0 {
+        block = block.checked_sub(1)?;
+        offset += read_u16(bytes, d.block_length + 2 * block)? as i64 + 1;
+    }
+    while offset > read_u16(bytes, d.block_length + 2 * block)? as i64 {
+        offset -= read_u16(bytes, d.block_length + 2 * block)? as i64 + 1;
+        block += 1;
+    }
+
+    //Decode symbols until reaching the one holding the offset
+    let mut ptr = d.data + block * d.block_size;
+    let mut buf64 = read_u64_be(bytes, ptr)?;
+    ptr += 8;
+    let mut buf64_size = 64;
+    let min_sym_len = d.min_sym_len as usize;
+
+    let mut sym;
+    loop {
+        let mut len = 0;
+        while buf64 < d.base64[len] {
+            len += 1;
+        }
+
+        sym = ((buf64 - d.base64[len]).checked_shr((64 - len - min_sym_len) as u32).unwrap_or(0) as usize)
+            .wrapping_add(read_u16(bytes, d.lowest_sym + 2 * len)? as usize) & 0xFFFF;
+
+        let values = *d.symlen.get(sym)? as i64 + 1;
+        if offset < values {
+            break;
+        }
+        offset -= values;
+
+        len += min_sym_len;
+        buf64 = buf64.checked_shl(len as u32).unwrap_or(0);
+        buf64_size -= len as i32;
+        if buf64_size <= 32 {
+            buf64_size += 32;
+            buf64 |= (read_u32_be(bytes, ptr)? as u64) << (64 - buf64_size);
+            ptr += 4;
+        }
+    }
+
+    //Expand the pairs down to a single value
+    while *d.symlen.get(sym)? != 0 {
+        let left = btree_left(bytes, d.btree, sym)?;
+        let left_values = *d.symlen.get(left)? as i64 + 1;
+        if offset < left_values {
+            sym = left;
+        }
+        else {
+            offset -= left_values;
+            sym = btree_right(bytes, d.btree, sym)?;
+        }
+    }
+
+    Some(btree_left(bytes, d.btree, sym)? as i32)
+}
+
+///Probes a table, without looking at captures
+fn probe_table(game: &Game, kind: TableKind, wdl: i32, state: &mut ProbeState) -> i32 {
+    if game.all_occupancies.to_u64().count_ones() == 2 {
+        return WDL_DRAW;
+    }
+
+    let tablebases = TABLEBASES.read().unwrap();
+    let tablebases = tablebases.as_deref();
+    let entry = tablebases.and_then(|t| t.keys.get(&material_signature(game)).map(|i| &t.entries[*i]));
+
+    match (tablebases, entry) {
+        (Some(tablebases), Some(entry)) => match entry.data(kind, &tablebases.paths) {
+            Some(data) => match data.index(game, entry, kind) {
+                Ok((stm, file, idx)) => {
+                    let value = decompress_pairs(data.get(entry, kind, stm, file), &data.bytes, idx).and_then(|value| data.map_score(entry, kind, file, value, wdl));
+                    value.unwrap_or_else(|| {
+                        *state = ProbeState::Fail;
+                        0
+                    })
+                },
+                Err(error) => {
+                    *state = error;
+                    0
+                }
+            },
+            None => {
+                *state = ProbeState::Fail;
+                0
+            }
+        },
+        _ => {
+            *state = ProbeState::Fail;
+            0
+        }
+    }
+}
+
+impl TableData {
+    ///The side to move and leading pawn file of the table storing the position, and its index there
+    fn index(&self, game: &Game, entry: &TableEntry, kind: TableKind) -> Result<(usize, usize, u64), ProbeState> {
+        let enc = encoding();
+        let black_to_move = game.active_player == Color::Black;
+
+        //The tables are stored with white as the stronger side, and only white to move when both sides have the same material
+        let symmetric_black_to_move = entry.key == entry.key2 && black_to_move;
+        let black_stronger = material_signature(game) != entry.key;
+        let flip = symmetric_black_to_move || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip ^ black_to_move) as usize;
+
+        //Squares from a1 = 0 and pieces as 1-6 for white and 9-14 for black
+        let mut board = vec![];
+        for piece in 0..12 {
+            let mut bits = game.bitboards[piece].to_u64();
+            while bits != 0 {
+                board.push((bits.trailing_zeros() as usize ^ 56, (piece % 6 + 1 + 8 * (piece / 6)) as u8));
+                bits &= bits - 1;
+            }
+        }
+        board.sort_unstable();
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut lead_pawn_piece = 0;
+        let mut tb_file = 0;
+
+        //With pawns there is a table for every file of the leading pawn, the one nearest the edge
+        if entry.has_pawns {
+            lead_pawn_piece = self.get(entry, kind, 0, 0).pieces[0] ^ flip_color;
+            for (sq, piece) in &board {
+                if *piece == lead_pawn_piece {
+                    squares[size] = sq ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+
+            let mut lead = 0;
+            for i in 1..lead_pawns {
+                if enc.map_pawns[squares[i]] > enc.map_pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            squares.swap(0, lead);
+
+            let file = squares[0] & 7;
+            tb_file = file.min(7 - file);
+        }
+
+        if kind == TableKind::Dtz {
+            let flags = self.get(entry, kind, stm, tb_file).flags;
+            if (flags & FLAG_STM) as usize != stm && (entry.key != entry.key2 || entry.has_pawns) {
+                return Err(ProbeState::ChangeStm);
+            }
+        }
+
+        for (sq, piece) in &board {
+            if entry.has_pawns && *piece == lead_pawn_piece {
+                continue;
+            }
+            if size == TB_PIECES {
+                return Err(ProbeState::Fail);
+            }
+            squares[size] = sq ^ flip_squares;
+            pieces[size] = piece ^ flip_color;
+            size += 1;
+        }
+
+        let d = self.get(entry, kind, stm, tb_file);
+
+        //Order the pieces like the table
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        //Mirror the leading piece to the a-d files
+        if squares[0] & 7 > 3 {
+            for sq in squares.iter_mut().take(size) {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx;
+        if entry.has_pawns {
+            idx = enc.lead_pawn_idx[lead_pawns][squares[0]];
+
+            squares[1..lead_pawns].sort_by_key(|s| enc.map_pawns[*s]);
+            for (i, sq) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += enc.binomial[i][enc.map_pawns[*sq]];
+            }
+        }
+        else {
+            //Mirror the leading piece to the first four ranks, and below the a1-h8 diagonal
+            if squares[0] >> 3 > 3 {
+                for sq in squares.iter_mut().take(size) {
+                    *sq ^= 56;
+                }
+            }
+
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for sq in squares.iter_mut().take(size).skip(i) {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if entry.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as u64;
+                let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+                let rank = |s: usize| (s >> 3) as u64;
+
+                idx = if off_a1h8(s0) != 0 {
+                    (enc.map_a1d1d4[s0] as u64 * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64 - adjust2
+                }
+                else if off_a1h8(s1) != 0 {
+                    (6 * 63 + rank(s0) * 28 + enc.map_b1h1h7[s1]) * 62 + s2 as u64 - adjust2
+                }
+                else if off_a1h8(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + rank(s0) * 7 * 28 + (rank(s1) - adjust1) * 28 + enc.map_b1h1h7[s2]
+                }
+                else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(s0) * 7 * 6 + (rank(s1) - adjust1) * 6 + (rank(s2) - adjust2)
+                };
+            }
+            else {
+                idx = enc.map_kk[enc.map_a1d1d4[squares[0]]][squares[1]];
+            }
+        }
+
+        //Encode the remaining groups, skipping the squares taken by earlier groups
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = entry.has_pawns && entry.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|s| sq > **s).count();
+                n += enc.binomial[i + 1][sq - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        Ok((stm, tb_file, idx))
+    }
+}
+
+fn legal_moves(game: &Game) -> Vec<Move> {
+    let mut copy = *game;
+    generate_moves(&mut copy, MoveTypes::All).legal_values(game)
+}
+
+///WDL of the position, looking at captures (and pawn moves) because the tables store "don't care" values when they are best
+fn search(game: &Game, state: &mut ProbeState, check_zeroing: bool) -> i32 {
+    let mut best_value = WDL_LOSS;
+    let moves = legal_moves(game);
+    let mut move_count = 0;
+
+    for m in &moves {
+        if !m.is_capture() && (!check_zeroing || m.piece() % 6 != Piece::WhitePawn as u8) {
+            continue;
+        }
+        move_count += 1;
+
+        let mut copy = *game;
+        make_move(&mut copy, m);
+        let value = -search(&copy, state, false);
+
+        if *state == ProbeState::Fail {
+            return WDL_DRAW;
+        }
+
+        if value > best_value {
+            best_value = value;
+            if value >= WDL_WIN {
+                *state = ProbeState::ZeroingBestMove;
+                return value;
+            }
+        }
+    }
+
+    //If every legal move was searched the stored value is not needed, and may be wrong with en passant
+    let no_more_moves = move_count > 0 && move_count == moves.len();
+    let value = if no_more_moves {
+        best_value
+    }
+    else {
+        let value = probe_table(game, TableKind::Wdl, WDL_DRAW, state);
+        if *state == ProbeState::Fail {
+            return WDL_DRAW;
+        }
+        value
+    };
+
+    if best_value >= value {
+        *state = if best_value > WDL_DRAW || no_more_moves { ProbeState::ZeroingBestMove } else { ProbeState::Ok };
+        return best_value;
+    }
+
+    *state = ProbeState::Ok;
+    value
+}
+
+///The win/draw/loss result of the position, or None if the tables can't tell
+pub fn probe_wdl(game: &Game) -> Option<i32> {
+    let mut state = ProbeState::Ok;
+    let wdl = search(game, &mut state, false);
+
+    if state == ProbeState::Fail { None } else { Some(wdl) }
+}
+
+//The DTZ tables do not store zeroing moves, but the distance before them follows from the WDL
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        WDL_WIN => 1,
+        WDL_CURSED_WIN => 101,
+        WDL_BLESSED_LOSS => -101,
+        WDL_LOSS => -1,
+        _ => 0,
+    }
+}
+
+///Plies to the next zeroing move with best play, positive when winning. 100 is added for cursed wins and blessed losses
+fn probe_dtz(game: &Game, state: &mut ProbeState) -> i32 {
+    *state = ProbeState::Ok;
+    let wdl = search(game, state, true);
+
+    if *state == ProbeState::Fail || wdl == WDL_DRAW {
+        return 0;
+    }
+
+    if *state == ProbeState::ZeroingBestMove {
+        return dtz_before_zeroing(wdl);
+    }
+
+    let dtz = probe_table(game, TableKind::Dtz, wdl, state);
+
+    if *state == ProbeState::Fail {
+        return 0;
+    }
+
+    if *state != ProbeState::ChangeStm {
+        return (dtz + if wdl == WDL_BLESSED_LOSS || wdl == WDL_CURSED_WIN { 100 } else { 0 }) * wdl.signum();
+    }
+
+    //The table stores the other side to move, so pick the best reply one ply deeper
+    let mut min_dtz = 0xFFFF;
+    for m in legal_moves(game) {
+        let zeroing = m.is_capture() || m.piece() % 6 == Piece::WhitePawn as u8;
+
+        let mut copy = *game;
+        make_move(&mut copy, &m);
+
+        let mut dtz = if zeroing { -dtz_before_zeroing(search(&copy, state, false)) } else { -probe_dtz(&copy, state) };
+
+        //A mating move
+        if dtz == 1 && copy.is_in_check(copy.active_player) && legal_moves(&copy).is_empty() {
+            min_dtz = 1;
+        }
+
+        if !zeroing {
+            dtz += dtz.signum();
+        }
+
+        if dtz < min_dtz && dtz.signum() == wdl.signum() {
+            min_dtz = dtz;
+        }
+
+        if *state == ProbeState::Fail {
+            return 0;
+        }
+    }
+
+    if min_dtz == 0xFFFF { -1 } else { min_dtz }
+}
+
+///The root move that keeps the best result within the 50-move rule and converts it the fastest, with its score
+pub fn probe_root(game: &Game) -> Option<(Move, i32)> {
+    let mut state = ProbeState::Ok;
+    let half_moves = game.half_moves as i32;
+    let mut best: Option<(Move, i32, i32)> = None;
+
+    for m in legal_moves(game) {
+        let mut copy = *game;
+        make_move(&mut copy, &m);
+
+        let mut dtz = if copy.half_moves == 0 {
+            state = ProbeState::Ok;
+            dtz_before_zeroing(-search(&copy, &mut state, false))
+        }
+        else {
+            let dtz = -probe_dtz(&copy, &mut state);
+            dtz + dtz.signum()
+        };
+
+        if dtz == 2 && copy.is_in_check(copy.active_player) && legal_moves(&copy).is_empty() {
+            dtz = 1;
+        }
+
+        if state == ProbeState::Fail {
+            return None;
+        }
+
+        //Wins and losses are ranked equally unless the 50-move rule is in sight
+        let rank = if dtz > 0 {
+            if dtz + half_moves <= 99 { 1000 } else { 1000 - (dtz + half_moves) }
+        }
+        else if dtz < 0 {
+            if -dtz * 2 + half_moves < 100 { -1000 } else { -1000 + (-dtz + half_moves) }
+        }
+        else {
+            0
+        };
+
+        //Among equal ranks, win fast and lose slowly
+        if best.is_none_or(|(_, best_rank, best_dtz)| (rank, -dtz) > (best_rank, -best_dtz)) {
+            best = Some((m, rank, dtz));
+        }
+    }
+
+    let (m, rank, dtz) = best?;
+    let score = if rank >= 900 { TB_WIN - dtz } else if rank <= -900 { -TB_WIN - dtz } else { 0 };
+
+    Some((m, score))
+}
+
+fn read_u8(bytes: &[u8], at: usize) -> Option<u8> {
+    bytes.get(at).copied()
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+//Every node of the pair tree is 3 bytes, the left and right symbols as 12 bits each
+fn btree_left(bytes: &[u8], btree: usize, sym: usize) -> Option<usize> {
+    let node = bytes.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some((((node[1] & 0xF) as usize) << 8) | node[0] as usize)
+}
+
+fn btree_right(bytes: &[u8], btree: usize, sym: usize) -> Option<usize> {
+    let node = bytes.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    Some(((node[2] as usize) << 4) | (node[1] >> 4) as usize)
+}
+
+#[cfg(test)]
+mod syzygy_tests {
+    use super::*;
+
+    #[test]
+    pub fn encoding_tables() {
+        let enc = encoding();
+
+        //462 king placements, the last ones with both kings on the diagonal
+        let codes: Vec<u64> = (0..10).flat_map(|i| enc.map_kk[i].iter().copied()).collect();
+        assert_eq!(codes.iter().max(), Some(&461));
+        assert_eq!(enc.map_a1d1d4[1], 0); //b1
+        assert_eq!(enc.map_a1d1d4[27], 9); //d4
+
+        assert_eq!(enc.binomial[2][6], 15);
+        assert_eq!(enc.binomial[5][63], 7028847);
+
+        //The a2 pawn is the furthest toward the edge, e7 the least
+        assert_eq!(enc.map_pawns[8], 47);
+        assert_eq!(enc.map_pawns[15], 46);
+        assert_eq!(enc.map_pawns[52], 0);
+        assert_eq!(enc.lead_pawns_size[1][0], 6);
+    }
+
+    #[test]
+    pub fn table_codes_up_to_five_pieces() {
+        let codes = table_codes();
+        assert_eq!(codes.iter().filter(|c| c.len() <= 6).count(), 145);
+        assert!(codes.contains(&"KRvK".to_string()));
+        assert!(codes.contains(&"KQPvKR".to_string()));
+        assert!(codes.contains(&"KRRvKQ".to_string()));
+    }
+
+    #[test]
+    pub fn table_entry() {
+        let entry = TableEntry::new("KRPvKP");
+        assert!(entry.has_pawns && entry.has_unique_pieces);
+        assert_eq!(entry.pawn_count, [1, 1]);
+        assert_eq!(entry.piece_count, 5);
+
+        let game = Game::new_from_fen("8/4p3/8/2k5/8/8/1KR1P3/8 w - - 0 1").unwrap();
+        assert_eq!(material_signature(&game), entry.key);
+        let game = Game::new_from_fen("8/1kr1p3/8/8/2K5/8/4P3/8 w - - 0 1").unwrap();
+        assert_eq!(material_signature(&game), entry.key2);
+
+        //Black leads with fewer pawns
+        let entry = TableEntry::new("KPPvKP");
+        assert_eq!(entry.pawn_count, [1, 2]);
+        assert!(!TableEntry::new("KNNvK").has_unique_pieces);
+    }
+
+    #[test]
+    pub fn probing_without_tables_fails() {
+        let game = Game::new_from_fen("8/8/8/3k4/8/8/8/RN2K3 w - - 0 1").unwrap();
+        assert!(!in_tablebases(&game));
+        assert_eq!(probe_wdl(&game), None);
+        assert!(probe_root(&game).is_none());
+        assert!(init_tablebases("/nonexistent").is_err());
+    }
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("syzygy")
+    }
+
+    fn root_move(fen: &str) -> (String, i32) {
+        let (m, score) = probe_root(&Game::new_from_fen(fen).unwrap()).unwrap();
+        (m.to_uci(), score)
+    }
+
+    fn dtz(fen: &str) -> i32 {
+        let mut state = ProbeState::Ok;
+        let dtz = probe_dtz(&Game::new_from_fen(fen).unwrap(), &mut state);
+        assert!(state != ProbeState::Fail);
+        dtz
+    }
+
+    //The only test loading tables, so no other test sees them change
+    #[test]
+    pub fn probes_fixture_tables() {
+        assert_eq!(init_tablebases(fixtures().to_str().unwrap()), Ok(5));
+        //Setting the path again replaces the tables
+        assert_eq!(init_tablebases(fixtures().to_str().unwrap()), Ok(5));
+        assert_eq!(tablebase_pieces(), 3);
+
+        let wdl = |fen: &str| probe_wdl(&Game::new_from_fen(fen).unwrap());
+
+        //KRvK is won unless the rook hangs, from either color
+        assert_eq!(wdl("8/8/8/3k4/8/8/8/R3K3 w - - 0 1"), Some(WDL_WIN));
+        assert_eq!(wdl("8/8/8/3k4/8/8/8/R3K3 b - - 0 1"), Some(WDL_LOSS));
+        assert_eq!(wdl("3k3r/8/8/8/8/8/8/4K3 w - - 0 1"), Some(WDL_LOSS));
+        assert_eq!(wdl("8/8/8/8/8/8/kR6/4K3 b - - 0 1"), Some(WDL_DRAW));
+        assert_eq!(wdl("7k/8/8/8/8/8/8/KB6 w - - 0 1"), Some(WDL_DRAW));
+
+        //The king in front of the pawn on the sixth rank wins, unless it is a stalemate
+        assert_eq!(wdl("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Some(WDL_WIN));
+        assert_eq!(wdl("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Some(WDL_LOSS));
+        assert_eq!(wdl("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(WDL_DRAW));
+        assert_eq!(wdl("k7/8/K7/P7/8/8/8/8 w - - 0 1"), Some(WDL_DRAW));
+        assert_eq!(wdl("K7/8/8/8/8/8/p7/7k w - - 0 1"), Some(WDL_LOSS));
+
+        //The rule of the square
+        assert_eq!(wdl("7k/8/8/8/P7/8/8/K7 b - - 0 1"), Some(WDL_LOSS));
+        assert_eq!(wdl("4k3/8/8/8/P7/8/8/K7 b - - 0 1"), Some(WDL_DRAW));
+
+        //Mate in one, and the mated side
+        assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R w - - 0 1"), 1);
+        assert_eq!(dtz("k6R/8/1K6/8/8/8/8/8 b - - 0 1"), -1);
+        assert_eq!(root_move("k7/8/1K6/8/8/8/8/7R w - - 0 1"), ("h1h8".to_string(), TB_WIN - 1));
+
+        //A won KRvK takes longer than mating in one, and losing keeps the same distance
+        let won = dtz("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+        assert!(won > 1 && won <= 31 && won % 2 == 1);
+        let mut game = Game::new_from_fen("8/8/8/3k4/8/8/8/R3K3 w - - 0 1").unwrap();
+        let (m, score) = probe_root(&game).unwrap();
+        assert_eq!(score, TB_WIN - won);
+        make_move(&mut game, &m);
+        let mut state = ProbeState::Ok;
+        assert_eq!(probe_dtz(&game, &mut state), -(won - 1));
+
+        //Pushing the pawn resets the 50-move counter at once
+        assert_eq!(dtz("7k/8/8/8/P7/8/8/K7 w - - 0 1"), 1);
+        assert_eq!(root_move("7k/8/8/8/P7/8/8/K7 w - - 0 1"), ("a4a5".to_string(), TB_WIN - 1));
+        assert_eq!(root_move("k7/8/K7/P7/8/8/8/8 w - - 0 1").1, 0);
+
+        //Here the pawn is blocked, so the king has to make way first
+        let won = dtz("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1");
+        assert!(won > 1);
+        assert_eq!(root_move("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").1, TB_WIN - won);
+        assert!(dtz("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") < 0);
+
+        //A path without tables keeps the loaded ones, an empty path unloads them
+        assert!(init_tablebases("/nonexistent").is_err());
+        assert_eq!(tablebase_pieces(), 3);
+        assert_eq!(init_tablebases("<empty>"), Ok(0));
+        assert_eq!(tablebase_pieces(), 0);
+        assert_eq!(probe_wdl(&Game::new_from_fen("8/8/8/3k4/8/8/8/R3K3 w - - 0 1").unwrap()), None);
+    }
+
+    //Retrograde analysis of the endgames up to three pieces, to write the fixture tables
+    struct Solved {
+        code: String,
+        pieces: Vec<usize>,
+        valid: Vec<bool>,
+        wdl: Vec<i8>,
+        dtz: Vec<i16>,
+    }
+
+    const UNKNOWN: i8 = i8::MIN;
+
+    //Positions are numbered by the squares of the pieces and the side to move
+    fn position_id(game: &Game, pieces: &[usize]) -> usize {
+        let squares = pieces.iter().fold(0, |id, p| id * 64 + game.bitboards[*p].to_u64().trailing_zeros() as usize);
+        squares * 2 + (game.active_player == Color::Black) as usize
+    }
+
+    fn position_fen(pieces: &[usize], id: usize) -> Option<String> {
+        let mut board = [None; 64];
+        let mut rest = id / 2;
+        for piece in pieces.iter().rev() {
+            let sq = rest % 64;
+            rest /= 64;
+            if board[sq].is_some() || (piece % 6 == 0 && !(8..56).contains(&sq)) {
+                return None;
+            }
+            board[sq] = Some("PNBRQKpnbrqk".as_bytes()[*piece] as char);
+        }
+
+        let mut fen = String::new();
+        for rank in board.chunks(8) {
+            let mut empty = 0;
+            for sq in rank {
+                match sq {
+                    Some(c) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                        }
+                        empty = 0;
+                        fen.push(*c);
+                    },
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            fen.push('/');
+        }
+        fen.pop();
+        fen.push_str(if id.is_multiple_of(2) { " w - - 0 1" } else { " b - - 0 1" });
+        Some(fen)
+    }
+
+    fn position(pieces: &[usize], id: usize) -> Option<Game> {
+        let game = Game::new_from_fen(&position_fen(pieces, id)?)?;
+        if game.is_in_check(opposite_color(game.active_player)) { None } else { Some(game) }
+    }
+
+    fn solve(code: &str, pieces: &[usize], solved: &[Solved]) -> Solved {
+        let size = 2 << (6 * pieces.len());
+        let mut valid = vec![false; size];
+        let mut mated = vec![false; size];
+        //Moves staying in the table as the position and if they zero the counter, and the results of the ones leaving it
+        let mut children: Vec<Vec<(usize, bool)>> = vec![vec![]; size];
+        let mut exits: Vec<Vec<i8>> = vec![vec![]; size];
+
+        for id in 0..size {
+            let Some(game) = position(pieces, id) else { continue };
+            valid[id] = true;
+
+            let moves = legal_moves(&game);
+            mated[id] = moves.is_empty() && game.is_in_check(game.active_player);
+            for m in moves {
+                let mut copy = game;
+                make_move(&mut copy, &m);
+                if material_signature(&copy) == material_signature(&game) {
+                    children[id].push((position_id(&copy, pieces), m.piece() % 6 == Piece::WhitePawn as u8));
+                }
+                else if copy.all_occupancies.to_u64().count_ones() == 2 {
+                    exits[id].push(0);
+                }
+                else {
+                    let table = solved.iter().find(|t| signature_from_str(&t.code) == material_signature(&copy)).unwrap();
+                    exits[id].push(table.wdl[position_id(&copy, &table.pieces)]);
+                }
+            }
+        }
+
+        //Won if a move leaves the opponent lost, lost if every move leaves them winning
+        let mut wdl: Vec<i8> = (0..size).map(|id| if !valid[id] || mated[id] { -2 } else if children[id].is_empty() && exits[id].is_empty() { 0 } else { UNKNOWN }).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in 0..size {
+                if wdl[id] != UNKNOWN {
+                    continue;
+                }
+                let mut results = exits[id].iter().copied().chain(children[id].iter().map(|(child, _)| wdl[*child]));
+                if results.clone().any(|r| r == -2) {
+                    wdl[id] = 2;
+                    changed = true;
+                }
+                else if results.all(|r| r == 2) {
+                    wdl[id] = -2;
+                    changed = true;
+                }
+            }
+        }
+        for id in 0..size {
+            if !valid[id] || wdl[id] == UNKNOWN {
+                wdl[id] = 0;
+            }
+        }
+
+        //Plies to a zeroing move or mate, the winner takes the shortest way and the loser the longest
+        let mut dtz = vec![0i16; size];
+        let mut plies = 1;
+        while (0..size).any(|id| wdl[id] != 0 && dtz[id] == 0) {
+            assert!(plies <= 100, "{} has cursed wins", code);
+            let previous = dtz.clone();
+            for id in 0..size {
+                if wdl[id] == 0 || dtz[id] != 0 {
+                    continue;
+                }
+                if wdl[id] == 2 {
+                    let now = exits[id].contains(&-2) || children[id].iter().any(|(child, zeroing)| wdl[*child] == -2 && (*zeroing || mated[*child]));
+                    let later = plies > 1 && children[id].iter().any(|(child, zeroing)| !zeroing && previous[*child] == 1 - plies);
+                    if (plies == 1 && now) || later {
+                        dtz[id] = plies;
+                    }
+                }
+                else {
+                    let quiet: Vec<i16> = children[id].iter().filter(|(_, zeroing)| !zeroing).map(|(child, _)| previous[*child]).collect();
+                    if quiet.iter().all(|d| *d != 0) {
+                        dtz[id] = -quiet.iter().map(|d| d + 1).max().unwrap_or(1).max(1);
+                    }
+                }
+            }
+            plies += 1;
+        }
+
+        Solved { code: code.to_string(), pieces: pieces.to_vec(), valid, wdl, dtz }
+    }
+
+    const BLOCK_BITS: u8 = 5;
+    const SPAN_BITS: u8 = 8;
+
+    //One table of a file, with a fixed length code and a symbol for every distinct value
+    #[derive(Default)]
+    struct Packed {
+        sizes: Vec<u8>,
+        sparse_index: Vec<u8>,
+        block_lengths: Vec<u8>,
+        blocks: Vec<u8>,
+    }
+
+    fn pack(values: &[Option<u16>], flags: u8) -> Packed {
+        let mut used: Vec<u16> = values.iter().flatten().copied().collect();
+        used.sort_unstable();
+        used.dedup();
+        if used.len() <= 1 {
+            return Packed { sizes: vec![flags | FLAG_SINGLE_VALUE, used.first().copied().unwrap_or(0) as u8], ..Default::default() };
+        }
+
+        let len = (usize::BITS - (used.len() - 1).leading_zeros()) as usize;
+        let block_size = 1 << BLOCK_BITS;
+        let per_block = block_size * 8 / len;
+        let symbols: Vec<u64> = values.iter().map(|v| used.binary_search(&v.unwrap_or(used[0])).unwrap() as u64).collect();
+
+        let mut packed = Packed::default();
+        let blocks: Vec<&[u64]> = symbols.chunks(per_block).collect();
+        for block in &blocks {
+            let mut bits = vec![0u8; block_size];
+            for (i, sym) in block.iter().enumerate() {
+                for b in 0..len {
+                    if sym >> (len - 1 - b) & 1 != 0 {
+                        let at = i * len + b;
+                        bits[at / 8] |= 0x80 >> (at % 8);
+                    }
+                }
+            }
+            packed.blocks.extend(bits);
+            packed.block_lengths.extend((block.len() as u16 - 1).to_le_bytes());
+        }
+
+        //The middle value of every span, in the last block when it is past the end
+        let span = 1 << SPAN_BITS;
+        for k in 0..values.len().div_ceil(span) {
+            let middle = k * span + span / 2;
+            let block = (middle / per_block).min(blocks.len() - 1);
+            packed.sparse_index.extend((block as u32).to_le_bytes());
+            packed.sparse_index.extend(((middle - block * per_block) as u16).to_le_bytes());
+        }
+
+        packed.sizes = vec![flags, BLOCK_BITS, SPAN_BITS, 0];
+        packed.sizes.extend((blocks.len() as u32).to_le_bytes());
+        packed.sizes.extend([len as u8, len as u8, 0, 0]);
+        packed.sizes.extend((used.len() as u16).to_le_bytes());
+        for value in &used {
+            packed.sizes.extend([(value & 0xFF) as u8, (value >> 8) as u8 | 0xF0, 0xFF]);
+        }
+        if used.len() % 2 == 1 {
+            packed.sizes.push(0);
+        }
+        packed
+    }
+
+    fn write_table(table: &Solved, kind: TableKind) {
+        let entry = TableEntry::new(&table.code);
+        let files = if entry.has_pawns { 4 } else { 1 };
+        let sides = if kind == TableKind::Wdl { 2 } else { 1 };
+        let flags = if kind == TableKind::Wdl { 0 } else { FLAG_WIN_PLIES | FLAG_LOSS_PLIES };
+        //The leading pawns come first
+        let mut codes: Vec<u8> = table.pieces.iter().map(|p| (p % 6 + 1 + 8 * (p / 6)) as u8).collect();
+        codes.sort_by_key(|c| c & 7 != 1);
+
+        let mut data = TableData { bytes: vec![], items: Default::default(), map: 0 };
+        for side in data.items.iter_mut().take(sides) {
+            for (file, d) in side.iter_mut().enumerate().take(files) {
+                d.pieces[..codes.len()].copy_from_slice(&codes);
+                d.flags = flags;
+                set_groups(&entry, d, [0, 0xF], file).unwrap();
+            }
+        }
+
+        let mut values: Vec<Vec<Vec<Option<u16>>>> = data.items.iter().map(|side| side.iter().map(|d| vec![None; d.group_idx[d.group_len.iter().position(|l| *l == 0).unwrap()] as usize]).collect()).collect();
+        for id in (0..table.valid.len()).filter(|id| table.valid[*id]) {
+            let game = position(&table.pieces, id).unwrap();
+            let value = if kind == TableKind::Wdl { table.wdl[id] as u16 + 2 } else { table.dtz[id].unsigned_abs().max(1) - 1 };
+            if let Ok((stm, file, idx)) = data.index(&game, &entry, kind) {
+                let slot = &mut values[stm][file][idx as usize];
+                assert!(slot.is_none_or(|v| v == value), "{} differs between mirrored positions", table.code);
+                *slot = Some(value);
+            }
+        }
+
+        let mut bytes = if kind == TableKind::Wdl { WDL_MAGIC.to_vec() } else { DTZ_MAGIC.to_vec() };
+        bytes.push(1 | (entry.has_pawns as u8) << 1);
+        for _ in 0..files {
+            bytes.push(0);
+            bytes.extend(codes.iter().map(|c| c | c << 4));
+        }
+        bytes.resize(bytes.len() + bytes.len() % 2, 0);
+
+        let packed: Vec<Packed> = (0..files).flat_map(|file| (0..sides).map(move |side| (side, file))).map(|(side, file)| pack(&values[side][file], flags)).collect();
+        for p in &packed {
+            bytes.extend(&p.sizes);
+        }
+        if kind == TableKind::Dtz {
+            bytes.resize(bytes.len() + bytes.len() % 2, 0);
+        }
+        for p in &packed {
+            bytes.extend(&p.sparse_index);
+        }
+        for p in &packed {
+            bytes.extend(&p.block_lengths);
+        }
+        for p in &packed {
+            bytes.resize((bytes.len() + 0x3F) & !0x3F, 0);
+            bytes.extend(&p.blocks);
+        }
+
+        let name = format!("{}{}", table.code, if kind == TableKind::Wdl { ".rtbw" } else { ".rtbz" });
+        fs::write(fixtures().join(name), bytes).unwrap();
+    }
+
+    //The fixtures are solved here rather than downloaded, run with --ignored to write them again
+    #[test]
+    #[ignore]
+    pub fn write_fixture_tables() {
+        use Piece::*;
+        let pieces = |extra: Piece| vec![WhiteKing as usize, extra as usize, BlackKing as usize];
+
+        let mut solved = vec![];
+        for (code, extra) in [("KQvK", WhiteQueen), ("KRvK", WhiteRook), ("KBvK", WhiteBishop), ("KNvK", WhiteKnight), ("KPvK", WhitePawn)] {
+            let table = solve(code, &pieces(extra), &solved);
+            solved.push(table);
+        }
+
+        //The longest KQvK and KRvK wins are mates in 10 and 16 moves
+        let longest = |code: &str| solved.iter().find(|t| t.code == code).unwrap().dtz.iter().copied().max();
+        assert_eq!(longest("KQvK"), Some(19));
+        assert_eq!(longest("KRvK"), Some(31));
+
+        fs::create_dir_all(fixtures()).unwrap();
+        for table in &solved {
+            write_table(table, TableKind::Wdl);
+            write_table(table, TableKind::Dtz);
+        }
+    }
+}
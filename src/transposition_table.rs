@@ -19,7 +19,7 @@ pub enum TranspositionTableEntry {
         depth: u8,
         flag: HashFlag,
         score: i32,
-        //best: Move
+        best: Move
     }
 }
 
@@ -28,13 +28,13 @@ pub struct TranspositionTable {
 }
 
 impl TranspositionTableEntry {
-    pub fn new(hash: u64, depth: u8, flag: HashFlag, score: i32) -> Self {
+    pub fn new(hash: u64, depth: u8, flag: HashFlag, score: i32, best: Move) -> Self {
         Self::Record {
             hash: hash,
             depth: depth,
             flag: flag,
             score: score,
-            //best: best 
+            best
         }
     }
 }
@@ -44,7 +44,14 @@ impl TranspositionTable {
         Self{table: vec![TranspositionTableEntry::Empty; TT_SIZE].into_boxed_slice()}
     }
 
-    pub fn record(&mut self, hash: u64, score: i32, depth: u8, flag: HashFlag, ply: u8) {
+    ///Table of roughly the given size in megabytes
+    pub fn with_size(megabytes: usize) -> Self {
+        let entries = (megabytes * 1_048_576) / std::mem::size_of::<TranspositionTableEntry>();
+        Self{table: vec![TranspositionTableEntry::Empty; entries.max(1)].into_boxed_slice()}
+    }
+
+    ///Stores a search result, with NULL_MOVE as the best move of fail-low nodes
+    pub fn record(&mut self, hash: u64, score: i32, depth: u8, flag: HashFlag, ply: u8, best: Move) {
         //Adjust mating scores before insertion
         let mut adjusted_score: i32 = score;
         if score < -MATE_BOUND {
@@ -53,15 +60,33 @@ impl TranspositionTable {
             adjusted_score += ply as i32;
         }
 
-        self.table[(hash % TT_SIZE as u64) as usize] = TranspositionTableEntry::new(hash, depth, flag, adjusted_score)
+        let index = (hash % self.table.len() as u64) as usize;
+
+        //Keep the old best move of the position when there is no new one
+        let mut best_move = best;
+        if let TranspositionTableEntry::Record { hash: old_hash, best: old_best, .. } = self.table[index] {
+            if best == NULL_MOVE && old_hash == hash {
+                best_move = old_best;
+            }
+        }
+
+        self.table[index] = TranspositionTableEntry::new(hash, depth, flag, adjusted_score, best_move)
+    }
+
+    ///The best move stored for the position, to be searched first
+    pub fn probe_move(&self, p_hash: u64) -> Option<Move> {
+        match self.table[(p_hash % self.table.len() as u64) as usize] {
+            TranspositionTableEntry::Record { hash, best, .. } if hash == p_hash && best != NULL_MOVE => Some(best),
+            _ => None
+        }
     }
 
     pub fn probe(&mut self, p_hash: u64, p_depth: u8, p_alpha: i32, p_beta: i32, ply: u8) -> i32 {
 
-        let entry = &self.table[(p_hash % TT_SIZE as u64) as usize];
+        let entry = &self.table[(p_hash % self.table.len() as u64) as usize];
 
         match entry {
-            TranspositionTableEntry::Record { hash, depth, flag, score } => {
+            TranspositionTableEntry::Record { hash, depth, flag, score, .. } => {
                 if p_hash == *hash {
                     if *depth >= p_depth {
                         //Adjust mating scores before extraction
@@ -106,6 +131,6 @@ mod tt_tests {
     pub fn tt () {
         let mut game = Game::new_from_fen("").unwrap();
         game.pretty_print();
-        search(&mut game, 4, -1, &IoWrapper::init(), &mut TranspositionTable::new(), &mut RepetitionTable::new());
+        search(&mut game, 4, -1, &IoWrapper::init(), &mut TranspositionTable::new(), &mut RepetitionTable::new(), &mut ClassicEvaluator::new());
     }
 }
\ No newline at end of file
@@ -40,6 +40,13 @@ pub fn opposite_color(color: Color) -> Color {
     if color == Color::White { Color::Black } else { Color::White }
 }
 
+///Number of king moves between two squares
+pub fn square_distance(a: u8, b: u8) -> i32 {
+    let file_distance = (a as i32 % 8 - b as i32 % 8).abs();
+    let rank_distance = (a as i32 / 8 - b as i32 / 8).abs();
+    file_distance.max(rank_distance)
+}
+
 pub fn square_from_string(string: &str) -> Square {
     let chars = string.as_bytes();
     let x = chars[0] - 97;
@@ -112,17 +119,6 @@ pub const LOOKUP_RANK: [usize; 64] =
 
 pub const PIECE_STRINGS: [&str; 13] = ["P", "N", "B", "R", "Q", "K", "p", "n", "b", "r", "q", "k", "None"];
 
-pub const CASTLING_RIGHTS: [u8; 64] = [
-    7, 15, 15, 15,  3, 15, 15, 11,
-   15, 15, 15, 15, 15, 15, 15, 15,
-   15, 15, 15, 15, 15, 15, 15, 15,
-   15, 15, 15, 15, 15, 15, 15, 15,
-   15, 15, 15, 15, 15, 15, 15, 15,
-   15, 15, 15, 15, 15, 15, 15, 15,
-   15, 15, 15, 15, 15, 15, 15, 15,
-   13, 15, 15, 15, 12, 15, 15, 14
-];
-
 #[derive(Copy, Clone)]
 #[derive(PartialEq)]
 #[allow(non_camel_case_types)]
@@ -167,6 +163,64 @@ pub const PIECE_KEYS: [[u64; 64]; 12] = generate_piece_keys();
 pub const ENPASSANT_KEYS: [u64; 64] = generate_enpassant_keys();
 pub const CASTLE_KEYS: [u64; 16] = generate_castle_keys();
 pub const SIDE_KEY: u64 = get_random_u64_number(4084590338).0;
+//Three-check counters, by color and checks given. No checks hash to 0, leaving other variants unchanged
+pub const CHECK_KEYS: [[u64; 4]; 2] = generate_check_keys();
+
+//Crazyhouse pocket counts, by color, piece % 6 and count up to 16. Empty pockets hash to 0
+pub const POCKET_KEYS: [[[u64; 17]; 5]; 2] = generate_pocket_keys();
+
+//Crazyhouse promoted piece marks, by square, as a promoted piece goes to the pocket as a pawn
+pub const PROMOTED_KEYS: [u64; 64] = generate_promoted_keys();
+
+const fn generate_promoted_keys() -> [u64; 64] {
+    let mut keys = [0; 64];
+
+    let mut sq = 0;
+    let mut state = 1714636915;
+
+    while sq < 64 {
+        let res = get_random_u64_number(state);
+        state = res.1;
+        keys[sq] = res.0;
+        sq+=1;
+    }
+
+    keys
+}
+
+const fn generate_pocket_keys() -> [[[u64; 17]; 5]; 2] {
+    let mut keys = [[[0; 17]; 5]; 2];
+
+    let mut i = 0;
+    let mut state = 2147483647;
+
+    while i < 2 * 5 * 16 {
+        let res = get_random_u64_number(state);
+        state = res.1;
+        keys[i / 80][i / 16 % 5][i % 16 + 1] = res.0;
+        i+=1;
+    }
+
+    keys
+}
+
+const fn generate_check_keys() -> [[u64; 4]; 2] {
+    let mut keys = [[0; 4]; 2];
+
+    let mut i = 1;
+    let mut state = 1804289383;
+
+    while i < 4 {
+        let white = get_random_u64_number(state);
+        let black = get_random_u64_number(white.1);
+        state = black.1;
+        keys[0][i] = white.0;
+        keys[1][i] = black.0;
+        i+=1;
+    }
+
+    keys
+}
 
 const fn generate_castle_keys() -> [u64; 16] {
     let mut keys = [0; 16];
@@ -234,7 +288,7 @@ const fn get_random_u32_number(state: u32) -> u32{
 }
 
 // generate 64-bit pseudo legal numbers
-const fn get_random_u64_number(state: u32) -> (u64, u32) {
+pub const fn get_random_u64_number(state: u32) -> (u64, u32) {
     // define 4 random numbers
     let n1 = get_random_u32_number(state);
     let n2 = get_random_u32_number(n1);
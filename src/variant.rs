@@ -0,0 +1,404 @@
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Variant {
+    Standard,
+    ///A king reaching one of the four center squares wins
+    KingOfTheHill,
+    ///Giving the third check wins
+    ThreeCheck,
+    ///Captures explode every piece but pawns around the capture square, capturer included
+    Atomic,
+    ///Captured pieces go to the capturer's pocket, and can be dropped on any empty square instead of moving
+    Crazyhouse,
+    ///White has 36 pawns and no king, and loses when all of them are captured
+    Horde
+}
+
+pub const VARIANTS: [Variant; 6] = [Variant::Standard, Variant::KingOfTheHill, Variant::ThreeCheck, Variant::Atomic, Variant::Crazyhouse, Variant::Horde];
+///Names used by the UCI_Variant option
+pub const VARIANT_NAMES: [&str; 6] = ["chess", "kingofthehill", "3check", "atomic", "crazyhouse", "horde"];
+
+pub const HORDE_START_FEN: &str = "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+
+///d5, e5, d4 and e4
+pub const HILL: u64 = 1 << Square::d5 as u64 | 1 << Square::e5 as u64 | 1 << Square::d4 as u64 | 1 << Square::e4 as u64;
+
+impl Variant {
+    pub fn from_name(name: &str) -> Option<Variant> {
+        let name = name.to_ascii_lowercase();
+        if name == "standard" {
+            return Some(Variant::Standard);
+        }
+        VARIANT_NAMES.iter().position(|n| *n == name).map(|i| VARIANTS[i])
+    }
+
+    pub fn name(&self) -> &'static str {
+        VARIANT_NAMES[*self as usize]
+    }
+
+    pub fn start_fen(&self) -> &'static str {
+        if *self == Variant::Horde { HORDE_START_FEN } else { START_FEN }
+    }
+}
+
+///Whether the side to move already lost by the rules of the variant, before any move generation
+#[inline(always)]
+pub fn is_variant_loss(game: &Game) -> bool {
+    let (own, opponent) = if game.active_player == Color::White { (0, 1) } else { (1, 0) };
+    match game.variant {
+        Variant::Standard => false,
+        Variant::KingOfTheHill => game.bitboards[opponent * 6 + 5].to_u64() & HILL != 0,
+        Variant::ThreeCheck => game.checks_given[opponent] >= 3,
+        Variant::Atomic => game.bitboards[own * 6 + 5].is_empty(),
+        Variant::Crazyhouse => false,
+        Variant::Horde => if own == 0 { game.white_occupancies.is_empty() } else { game.black_occupancies.is_empty() }
+    }
+}
+
+///Removes the capturing piece and every piece but pawns next to the capture square
+pub fn explode(game: &mut Game, square: u8) {
+    let blast = get_king_attack_table(square).to_u64() & !(game.bitboards[Piece::WhitePawn as usize].to_u64() | game.bitboards[Piece::BlackPawn as usize].to_u64());
+    let mut victims = Bitboard::from_u64((blast | 1 << square) & game.all_occupancies.to_u64());
+
+    while !victims.is_empty() {
+        let sq = victims.extract_bit();
+        let piece = (0..12).find(|p| game.bitboards[*p].get_bit(sq)).unwrap();
+
+        game.bitboards[piece].unset_bit(sq);
+        game.white_occupancies.unset_bit(sq);
+        game.black_occupancies.unset_bit(sq);
+        game.all_occupancies.unset_bit(sq);
+        game.zobrist_hash ^= PIECE_KEYS[piece][sq as usize];
+        if piece % 6 == Piece::WhitePawn as usize {
+            game.pawn_hash ^= PIECE_KEYS[piece][sq as usize];
+        }
+
+        //An exploded king or rook can't castle
+        game.castling_ability &= game.castling_rights_kept(sq);
+        if piece == Piece::WhiteKing as usize {
+            game.castling_ability &= !(CastlingAbility::WhiteKingSide as u8 | CastlingAbility::WhiteQueenSide as u8);
+        }
+        else if piece == Piece::BlackKing as usize {
+            game.castling_ability &= !(CastlingAbility::BlackKingSide as u8 | CastlingAbility::BlackQueenSide as u8);
+        }
+    }
+}
+
+///Atomic and Horde moves, tried on a copy. In Atomic the own king must survive, and be out of check unless the opponent king exploded.
+///Kings can't capture, as they would explode themselves
+pub fn is_legal_by_copy(game: &Game, cmove: &Move) -> bool {
+    if game.variant == Variant::Atomic && cmove.piece() % 6 == Piece::WhiteKing as u8 && cmove.is_capture() {
+        return false;
+    }
+    let mut copy = *game;
+    make_move(&mut copy, cmove)
+}
+
+///The pseudo-legal moves that are legal in Atomic or Horde
+pub fn generate_moves_by_copy(game: &Game, move_types: MoveTypes) -> MoveList {
+    let mut copy = *game;
+    let pseudo_legal = generate_moves(&mut copy, if move_types == MoveTypes::Quiescence { MoveTypes::Quiescence } else { MoveTypes::All });
+
+    let mut moves = MoveList::new();
+    for m in pseudo_legal.iter() {
+        if !is_legal_by_copy(game, m) {
+            continue;
+        }
+        if move_types == MoveTypes::QuietChecks {
+            let mut copy = *game;
+            make_move(&mut copy, m);
+            if m.is_capture() || !copy.is_in_check(copy.active_player) {
+                continue;
+            }
+        }
+        moves.add_move(*m);
+    }
+    moves
+}
+
+//Pawns can't be dropped on the first and last rank
+const BACK_RANKS: u64 = 0xff | 0xff << 56;
+
+///Puts a pawn to queen, by piece % 6, into the pocket of the side, 0 for white and 1 for black
+#[inline(always)]
+pub fn add_to_pocket(game: &mut Game, side: usize, piece_type: usize) {
+    let count = game.pockets[side][piece_type] as usize;
+    game.zobrist_hash ^= POCKET_KEYS[side][piece_type][count.min(16)] ^ POCKET_KEYS[side][piece_type][(count + 1).min(16)];
+    game.pockets[side][piece_type] += 1;
+}
+
+#[inline(always)]
+pub fn remove_from_pocket(game: &mut Game, side: usize, piece_type: usize) {
+    let count = game.pockets[side][piece_type] as usize;
+    game.zobrist_hash ^= POCKET_KEYS[side][piece_type][count.min(16)] ^ POCKET_KEYS[side][piece_type][(count - 1).min(16)];
+    game.pockets[side][piece_type] -= 1;
+}
+
+///The capturer gets the captured piece in its pocket, or a pawn if the piece was promoted
+#[inline(always)]
+pub fn pocket_capture(game: &mut Game, square: u8, piece: usize) {
+    let piece_type = if game.promoted.get_bit(square) {
+        game.promoted.unset_bit(square);
+        game.zobrist_hash ^= PROMOTED_KEYS[square as usize];
+        0
+    } else { piece % 6 };
+    add_to_pocket(game, if game.active_player == Color::White { 0 } else { 1 }, piece_type);
+}
+
+///Adds the drops of every piece in the pocket of the side to move on the target squares, which must be empty
+pub fn add_drops(game: &Game, moves: &mut MoveList, targets: u64) {
+    let (side, own) = if game.active_player == Color::White { (0, 0) } else { (1, 6) };
+    for piece_type in 0..5 {
+        if game.pockets[side][piece_type] == 0 {
+            continue;
+        }
+
+        let mut squares = Bitboard::from_u64(if piece_type == 0 { targets & !BACK_RANKS } else { targets });
+        while !squares.is_empty() {
+            moves.add_move(Move::new_drop(squares.extract_bit(), (own + piece_type) as u8));
+        }
+    }
+}
+
+///Whether a drop, like a hash or killer move, can be played here. Only the own king may still be left in check
+pub fn is_pseudo_legal_drop(game: &Game, cmove: &Move) -> bool {
+    let (side, own) = if game.active_player == Color::White { (0, 0) } else { (1, 6) };
+    let (to, piece) = (cmove.to_square(), cmove.piece() as usize);
+
+    game.variant == Variant::Crazyhouse
+        && (own..own + 5).contains(&piece)
+        && game.pockets[side][piece - own] > 0
+        && !game.all_occupancies.get_bit(to)
+        && (piece != own || BACK_RANKS & 1 << to == 0)
+}
+
+#[cfg(test)]
+mod variant_tests {
+    use super::*;
+
+    fn search_score(game: &mut Game, depth: i8) -> (Move, i32) {
+        let mut tt = TranspositionTable::with_size(1);
+        let mut rep_table = RepetitionTable::new();
+        let mut evaluator = ClassicEvaluator::new();
+        let mut envir = SearchEnv::new(-1, None, &mut tt, &mut rep_table, &mut evaluator);
+        envir.silent = true;
+        let result = iterative_deepening(game, depth, &mut envir);
+        (result.best_move, result.score)
+    }
+
+    #[test]
+    pub fn parses_names() {
+        assert!(Variant::from_name("3check") == Some(Variant::ThreeCheck));
+        assert!(Variant::from_name("KingOfTheHill") == Some(Variant::KingOfTheHill));
+        assert!(Variant::from_name("standard") == Some(Variant::Standard));
+        assert!(Variant::from_name("horde") == Some(Variant::Horde));
+        assert!(Variant::from_name("antichess").is_none());
+        assert_eq!(Variant::Atomic.name(), "atomic");
+    }
+
+    #[test]
+    pub fn king_of_the_hill() {
+        let mut game = Game::new_variant_from_fen("4k3/8/8/8/8/4K3/8/8 w - - 0 1", Variant::KingOfTheHill).unwrap();
+        let (best_move, score) = search_score(&mut game, 3);
+        assert!(HILL & 1 << best_move.to_square() != 0);
+        assert_eq!(score, MATE_VALUE - 1);
+
+        let m = game.parse_move("e3e4".to_string()).unwrap();
+        make_move(&mut game, &m);
+        assert!(is_variant_loss(&game));
+        assert_eq!(generate_legal_moves(&game, MoveTypes::All).len(), 0);
+
+        //Standard rules have no hill
+        let game = Game::new_from_fen("4k3/8/8/8/4K3/8/8/8 b - - 0 1").unwrap();
+        assert!(!is_variant_loss(&game));
+    }
+
+    #[test]
+    pub fn three_check() {
+        let mut game = Game::new_variant_from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 2+3 0 1", Variant::ThreeCheck).unwrap();
+        assert_eq!(game.checks_given, [1, 0]);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/R3K3 w - - 2+3 0 1");
+
+        for m in ["a1a8", "e8e7", "a8a7"] {
+            let m = game.parse_move(m.to_string()).unwrap();
+            make_move(&mut game, &m);
+            assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+        }
+        assert_eq!(game.checks_given, [3, 0]);
+        assert!(is_variant_loss(&game));
+        assert_eq!(game.to_fen(), "8/R3k3/8/8/8/8/8/4K3 b - - 0+3 3 2");
+
+        //Two checks given, any check wins
+        let mut game = Game::new_variant_from_fen("4k3/ppp2ppp/8/8/8/8/8/3QK3 w - - 1+3 0 1", Variant::ThreeCheck).unwrap();
+        let (_, score) = search_score(&mut game, 3);
+        assert_eq!(score, MATE_VALUE - 1);
+    }
+
+    #[test]
+    pub fn atomic_explosions() {
+        //Nxd7 explodes the knight, the queen on d8, the bishop on c8 and the king on e8, but not the pawns
+        let mut game = Game::new_variant_from_fen("rnbqkbnr/pppppppp/8/2N5/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1", Variant::Atomic).unwrap();
+        let capture = game.parse_move("c5d7".to_string()).unwrap();
+        assert!(make_move(&mut game, &capture));
+        assert_eq!(game.to_fen(), "rn3bnr/ppp1pppp/8/8/8/8/PPPPPPPP/R1BQKBNR b KQ - 0 1");
+        assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+        assert_eq!(game.pawn_hash, game.make_pawn_hash());
+        assert!(is_variant_loss(&game));
+
+        let mut game = Game::new_variant_from_fen("rnbqkbnr/pppppppp/8/2N5/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1", Variant::Atomic).unwrap();
+        let (best_move, score) = search_score(&mut game, 2);
+        assert!(best_move == capture);
+        assert_eq!(score, MATE_VALUE - 1);
+
+        //Kings can't capture, and touching kings can't check each other
+        let game = Game::new_variant_from_fen("8/8/8/8/8/3qk3/4K3/8 w - - 0 1", Variant::Atomic).unwrap();
+        assert!(!game.is_in_check(Color::White));
+        let moves: Vec<String> = generate_legal_moves(&game, MoveTypes::All).iter().map(|m| m.to_uci()).collect();
+        assert!(!moves.contains(&"e2d3".to_string()));
+        assert!(moves.contains(&"e2d2".to_string()));
+    }
+
+    #[test]
+    pub fn atomic_perft() {
+        let mut game = Game::new_variant_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", Variant::Atomic).unwrap();
+        let nodes: Vec<u128> = (1..5).map(|depth| perft(&mut game, depth, false)).collect();
+        assert_eq!(nodes, vec![20, 400, 8902, 197326]);
+    }
+
+    #[test]
+    pub fn crazyhouse_fen() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[Nq] w KQkq - 0 1";
+        let game = Game::new_variant_from_fen(fen, Variant::Crazyhouse).unwrap();
+        assert_eq!(game.pockets, [[0, 1, 0, 0, 0], [0, 0, 0, 0, 1]]);
+        assert_eq!(game.to_fen(), fen);
+
+        let ninth_rank = Game::new_variant_from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R/Nq w KQkq - 0 1", Variant::Crazyhouse).unwrap();
+        assert_eq!(ninth_rank.to_fen(), fen);
+        assert_eq!(ninth_rank.zobrist_hash, game.zobrist_hash);
+
+        let game = Game::new_variant_from_fen("4k3/8/8/8/8/8/8/Q~3K3[] w - - 0 1", Variant::Crazyhouse).unwrap();
+        assert!(game.promoted.get_bit(Square::a1 as u8));
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/Q~3K3[] w - - 0 1");
+        //The promoted mark is part of the hash
+        assert_ne!(game.zobrist_hash, Game::new_variant_from_fen("4k3/8/8/8/8/8/8/Q3K3[] w - - 0 1", Variant::Crazyhouse).unwrap().zobrist_hash);
+        assert!(Game::new_from_fen("4k3/8/8/8/8/8/8/4K3[K] w - - 0 1").is_none());
+    }
+
+    #[test]
+    pub fn crazyhouse_captures_and_drops() {
+        let mut game = Game::new_variant_from_fen("4k3/8/8/3p4/4P3/8/8/4K3[] w - - 0 1", Variant::Crazyhouse).unwrap();
+        for m in ["e4d5", "e8e7", "P@e6"] {
+            let m = game.parse_move(m.to_string()).unwrap();
+            if m.is_drop() {
+                assert_eq!(game.move_to_san(&m), "P@e6");
+            }
+            make_move(&mut game, &m);
+            assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+            assert_eq!(game.pawn_hash, game.make_pawn_hash());
+        }
+        assert_eq!(game.to_fen(), "8/4k3/4P3/3P4/8/8/8/4K3[] b - - 0 2");
+        assert!(game.parse_san("N@f5").is_none());
+
+        //A promoted queen goes to the pocket as a pawn
+        let mut game = Game::new_variant_from_fen("4k3/8/8/8/8/8/8/3q~K3[] w - - 0 1", Variant::Crazyhouse).unwrap();
+        let capture = game.parse_san("Kxd1").unwrap();
+        make_move(&mut game, &capture);
+        assert_eq!(game.pockets, [[1, 0, 0, 0, 0], [0; 5]]);
+        assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+        assert!(game.promoted.is_empty());
+
+        //The mark follows the promoted piece, in the hash too
+        let mut game = Game::new_variant_from_fen("4k3/1P6/8/8/8/8/8/4K3[] w - - 0 1", Variant::Crazyhouse).unwrap();
+        for m in ["b7b8q", "e8d7", "b8b5"] {
+            let m = game.parse_move(m.to_string()).unwrap();
+            make_move(&mut game, &m);
+            assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+        }
+        assert_eq!(game.to_fen(), "8/3k4/8/1Q~6/8/8/8/4K3[] b - - 2 2");
+    }
+
+    #[test]
+    pub fn crazyhouse_drops_block_checks() {
+        //Only drops on b1, c1 and d1 block the rook, and pawns can't be dropped on the first rank
+        let game = Game::new_variant_from_fen("4k3/8/8/8/8/8/8/r3K3[N] w - - 0 1", Variant::Crazyhouse).unwrap();
+        let mut legal: Vec<String> = generate_legal_moves(&game, MoveTypes::All).iter().map(|m| m.to_uci()).collect();
+        legal.sort();
+        assert_eq!(legal, vec!["N@b1", "N@c1", "N@d1", "e1d2", "e1e2", "e1f2"]);
+        let game = Game::new_variant_from_fen("4k3/8/8/8/8/8/8/r3K3[P] w - - 0 1", Variant::Crazyhouse).unwrap();
+        assert_eq!(generate_legal_moves(&game, MoveTypes::All).len(), 3);
+
+        //Drops not generated here, like hash or killer moves from other positions, are recognized
+        for fen in ["r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[Pq] w KQkq - 0 1",
+                    "4k3/8/8/8/8/8/8/r3K3[NPq] w - - 0 1",
+                    "4k3/8/8/8/8/8/8/r3K3[NPq] b - - 0 1"] {
+            let mut game = Game::new_variant_from_fen(fen, Variant::Crazyhouse).unwrap();
+            let legal = generate_legal_moves(&game, MoveTypes::All);
+            let pseudo_legal = generate_moves(&mut game, MoveTypes::All);
+            assert_eq!(pseudo_legal.legal_values(&game).len(), legal.len(), "{}", fen);
+            for m in pseudo_legal.iter() {
+                assert_eq!(is_valid_move(&game, m), legal.iter().any(|l| l == m), "{} {}", m.to_uci(), fen);
+            }
+            let queen_drop = Move::new_drop(Square::e4 as u8, Piece::BlackQueen as u8);
+            assert_eq!(is_pseudo_legal(&game, &queen_drop), game.active_player == Color::Black);
+        }
+    }
+
+    #[test]
+    pub fn crazyhouse_search() {
+        //Any rook drop from a8 to e8 mates
+        let mut game = Game::new_variant_from_fen("6k1/5ppp/8/8/8/8/8/K7[R] w - - 0 1", Variant::Crazyhouse).unwrap();
+        let (best_move, score) = search_score(&mut game, 2);
+        assert!(best_move.is_drop() && best_move.to_square() < Square::f8 as u8);
+        assert_eq!(score, MATE_VALUE - 1);
+    }
+
+    #[test]
+    pub fn crazyhouse_perft() {
+        let mut game = Game::new_variant_from_fen(START_FEN, Variant::Crazyhouse).unwrap();
+        let nodes: Vec<u128> = (1..6).map(|depth| perft(&mut game, depth, false)).collect();
+        assert_eq!(nodes, vec![20, 400, 8902, 197281, 4888832]);
+    }
+
+    #[test]
+    pub fn horde_moves() {
+        //The pawns on the first rank double push, but can't be taken en passant after it
+        let mut game = Game::new_variant_from_fen("4k3/8/8/8/8/8/8/P7 w - - 0 1", Variant::Horde).unwrap();
+        assert!(!game.is_in_check(Color::White));
+        let mut moves: Vec<String> = generate_legal_moves(&game, MoveTypes::All).iter().map(|m| m.to_uci()).collect();
+        moves.sort();
+        assert_eq!(moves, vec!["a1a2", "a1a3"]);
+        let m = game.parse_move("a1a3".to_string()).unwrap();
+        assert!(is_pseudo_legal(&game, &m));
+        make_move(&mut game, &m);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/P7/8/8 b - - 0 1");
+        assert_eq!(game.zobrist_hash, game.make_zobrist_hash());
+
+        //The Horde loses with its last pawn, and is stalemated without moves
+        let mut game = Game::new_variant_from_fen("8/8/8/8/8/8/k7/1P6 b - - 0 1", Variant::Horde).unwrap();
+        let m = game.parse_move("a2b1".to_string()).unwrap();
+        make_move(&mut game, &m);
+        assert!(is_variant_loss(&game));
+        let game = Game::new_variant_from_fen("8/8/8/8/8/k7/P7/K7 w - - 0 1", Variant::Standard).unwrap();
+        assert!(!is_variant_loss(&game));
+        let mut game = Game::new_variant_from_fen("k7/8/8/8/8/p7/P7/8 w - - 0 1", Variant::Horde).unwrap();
+        assert_eq!(generate_legal_moves(&game, MoveTypes::All).len(), 0);
+        assert_eq!(search_score(&mut game, 2).1, 0);
+    }
+
+    #[test]
+    pub fn horde_search() {
+        //Taking the last pawn wins
+        let mut game = Game::new_variant_from_fen("4k3/8/8/8/8/8/6P1/5q2 b - - 0 1", Variant::Horde).unwrap();
+        let (best_move, score) = search_score(&mut game, 2);
+        assert_eq!(best_move.to_uci(), "f1g2");
+        assert_eq!(score, MATE_VALUE - 1);
+    }
+
+    #[test]
+    pub fn horde_perft() {
+        let mut game = Game::new_variant_from_fen(Variant::Horde.start_fen(), Variant::Horde).unwrap();
+        let nodes: Vec<u128> = (1..6).map(|depth| perft(&mut game, depth, false)).collect();
+        assert_eq!(nodes, vec![8, 128, 1274, 23310, 265223]);
+    }
+}